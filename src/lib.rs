@@ -1,4 +1,4 @@
 #[macro_use]
-mod rllt;
+pub mod rllt;
 mod tests;
 
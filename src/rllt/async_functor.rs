@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::{pin, Pin};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use super::functor::{bind_args, hash_key, CacheKey, KeyFn, KwargsKey};
+use super::sync_functor::lock;
+
+// Outcome of one in-flight computation, shared with the calls waiting on it
+enum Flight<R> {
+    Running(Vec<Waker>),
+    Done(R),
+    // The computing call was dropped before finishing, waiters retry
+    Abandoned,
+}
+
+enum Slot<R> {
+    Ready(R),
+    Pending(Arc<Mutex<Flight<R>>>),
+}
+
+// Memoizing wrapper around an async function. Concurrent calls with the same key share
+// one computation: the first call drives the function's future and the others wait for
+// its result. Works with any executor, nothing is spawned
+pub struct AsyncF<Functor, Args, Kwargs, R> {
+    func: Functor,
+    args: Vec<Args>,
+    kwargs: HashMap<KwargsKey, Kwargs>,
+    key_fn: KeyFn<Args, Kwargs>,
+    slots: Mutex<HashMap<CacheKey<Args, Kwargs>, Slot<R>>>,
+}
+
+impl<Functor, Args, Kwargs, R> fmt::Debug for AsyncF<Functor, Args, Kwargs, R>
+where
+    Args: fmt::Debug,
+    Kwargs: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncF")
+            .field("func", &std::any::type_name::<Functor>())
+            .field("args", &self.args)
+            .field("kwargs", &self.kwargs)
+            .finish()
+    }
+}
+
+impl<Functor, Fut, Args, Kwargs, R> AsyncF<Functor, Args, Kwargs, R>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> Fut,
+    Fut: Future<Output = R>,
+    Args: Clone + PartialEq,
+    Kwargs: Clone + PartialEq,
+    R: Clone,
+{
+    pub fn new(func: Functor, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Self
+    where
+        Args: Hash + 'static,
+        Kwargs: Hash + 'static,
+    {
+        AsyncF::with_key_fn(func, args, kwargs, Arc::new(hash_key::<Args, Kwargs>))
+    }
+
+    pub fn with_key_fn(
+        func: Functor,
+        args: Vec<Args>,
+        kwargs: HashMap<KwargsKey, Kwargs>,
+        key_fn: KeyFn<Args, Kwargs>,
+    ) -> Self {
+        AsyncF {
+            func,
+            args,
+            kwargs,
+            key_fn,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn call(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> R {
+        let (all_args, all_kwargs) = bind_args(&self.args, &self.kwargs, args, kwargs);
+        let cache_key = CacheKey::new(all_args.clone(), all_kwargs.clone(), &self.key_fn);
+
+        loop {
+            let (flight, leader) = {
+                let mut slots = lock(&self.slots);
+                match slots.get(&cache_key) {
+                    Some(Slot::Ready(result)) => return result.clone(),
+                    Some(Slot::Pending(flight)) => (flight.clone(), false),
+                    None => {
+                        let flight = Arc::new(Mutex::new(Flight::Running(Vec::new())));
+                        slots.insert(cache_key.clone(), Slot::Pending(flight.clone()));
+                        (flight, true)
+                    }
+                }
+            };
+
+            if leader {
+                return self.compute(cache_key, flight, all_args, all_kwargs).await;
+            }
+            if let Some(result) = (Wait { flight }).await {
+                return result;
+            }
+        }
+    }
+
+    pub fn cache_len(&self) -> usize {
+        lock(&self.slots).values().filter(|slot| matches!(slot, Slot::Ready(_))).count()
+    }
+
+    // Computations still running keep their result
+    pub fn clear_cache(&self) {
+        lock(&self.slots).retain(|_, slot| matches!(slot, Slot::Pending(_)));
+    }
+
+    pub fn invalidate(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> bool {
+        let (all_args, all_kwargs) = bind_args(&self.args, &self.kwargs, args, kwargs);
+        let cache_key = CacheKey::new(all_args, all_kwargs, &self.key_fn);
+        let mut slots = lock(&self.slots);
+        if let Some(Slot::Ready(_)) = slots.get(&cache_key) {
+            slots.remove(&cache_key);
+            true
+        } else {
+            false
+        }
+    }
+
+    async fn compute(
+        &self,
+        cache_key: CacheKey<Args, Kwargs>,
+        flight: Arc<Mutex<Flight<R>>>,
+        args: Vec<Args>,
+        kwargs: HashMap<KwargsKey, Kwargs>,
+    ) -> R {
+        let mut guard = Abandon { slots: &self.slots, cache_key: Some(cache_key), flight: &flight };
+        let result = (self.func)(args, kwargs).await;
+
+        let cache_key = guard.cache_key.take().unwrap();
+        lock(&self.slots).insert(cache_key, Slot::Ready(result.clone()));
+        let waiting = std::mem::replace(&mut *lock(&flight), Flight::Done(result.clone()));
+        wake_all(waiting);
+        result
+    }
+}
+
+// Resolves to the leader's result, or None if the leader was dropped
+struct Wait<R> {
+    flight: Arc<Mutex<Flight<R>>>,
+}
+
+impl<R: Clone> Future for Wait<R> {
+    type Output = Option<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut *lock(&self.flight) {
+            Flight::Running(wakers) => {
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            Flight::Done(result) => Poll::Ready(Some(result.clone())),
+            Flight::Abandoned => Poll::Ready(None),
+        }
+    }
+}
+
+// Releases waiters and forgets the pending slot if the leader is dropped or panics
+struct Abandon<'a, Args: PartialEq, Kwargs: PartialEq, R> {
+    slots: &'a Mutex<HashMap<CacheKey<Args, Kwargs>, Slot<R>>>,
+    cache_key: Option<CacheKey<Args, Kwargs>>,
+    flight: &'a Mutex<Flight<R>>,
+}
+
+impl<Args: PartialEq, Kwargs: PartialEq, R> Drop for Abandon<'_, Args, Kwargs, R> {
+    fn drop(&mut self) {
+        if let Some(cache_key) = self.cache_key.take() {
+            lock(self.slots).remove(&cache_key);
+            wake_all(std::mem::replace(&mut *lock(self.flight), Flight::Abandoned));
+        }
+    }
+}
+
+fn wake_all<R>(flight: Flight<R>) {
+    if let Flight::Running(wakers) = flight {
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Runs a future to completion on the current thread, parking it while the future waits
+pub fn block_on<Fut: Future>(future: Fut) -> Fut::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::clock::{Clock, SystemClock};
+
+// Which entry goes when a bounded cache is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    Unbounded,
+    // Least recently used
+    Lru(usize),
+    // Least frequently used, ties broken by recency
+    Lfu(usize),
+    // Oldest insertion
+    Fifo(usize),
+}
+
+impl CachePolicy {
+    pub fn capacity(&self) -> Option<usize> {
+        match self {
+            CachePolicy::Unbounded => None,
+            CachePolicy::Lru(capacity) | CachePolicy::Lfu(capacity) | CachePolicy::Fifo(capacity) => Some(*capacity),
+        }
+    }
+}
+
+// Whether a functor returning Result keeps its Err results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCaching {
+    Cache,
+    // Every call failing so far is retried
+    Skip,
+    // Errors are kept for at most this long, or the cache's own TTL if that is shorter
+    Ttl(Duration),
+}
+
+// Counters since the cache was created or its stats were reset
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // Entries dropped to make room under the policy's capacity
+    pub evictions: u64,
+    // Entries dropped because their TTL ran out
+    pub expirations: u64,
+    pub size: usize,
+    pub capacity: Option<usize>,
+}
+
+impl CacheStats {
+    // Share of lookups served from the cache, 0 before the first lookup
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    value: V,
+    rank: (u64, u64),
+    hits: u64,
+    stored_at: SystemTime,
+    // Lifetime of this entry when shorter than the cache's
+    ttl: Option<Duration>,
+    // Key in `deadlines`, None when the entry never expires
+    deadline: Option<Deadline>,
+}
+
+// When an entry expires, and a tick that keeps equal times apart
+type Deadline = (SystemTime, u64);
+
+// Memo table with an eviction policy and optional time-to-live. Entries are ranked so
+// the next victim is always the first key of `order`, and the next to expire is the
+// first key of `deadlines`
+#[derive(Clone)]
+pub struct Cache<K, V> {
+    policy: CachePolicy,
+    entries: HashMap<K, Entry<V>>,
+    order: BTreeMap<(u64, u64), K>,
+    deadlines: BTreeMap<Deadline, K>,
+    tick: u64,
+    ttl: Option<Duration>,
+    refresh_on_access: bool,
+    clock: Arc<dyn Clock>,
+    stats: CacheStats,
+}
+
+impl<K, V> fmt::Debug for Cache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("policy", &self.policy)
+            .field("len", &self.entries.len())
+            .field("ttl", &self.ttl)
+            .field("refresh_on_access", &self.refresh_on_access)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(policy: CachePolicy) -> Self {
+        Cache {
+            policy,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            deadlines: BTreeMap::new(),
+            tick: 0,
+            ttl: None,
+            refresh_on_access: false,
+            clock: Arc::new(SystemClock),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    // Changing the policy drops every entry
+    pub fn set_policy(&mut self, policy: CachePolicy) {
+        self.clear();
+        self.policy = policy;
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    // Entries older than `ttl` are treated as missing. With `refresh_on_access` a hit
+    // restarts the entry's lifetime
+    pub fn set_ttl(&mut self, ttl: Option<Duration>, refresh_on_access: bool) {
+        self.ttl = ttl;
+        self.refresh_on_access = refresh_on_access;
+
+        self.deadlines.clear();
+        for (key, entry) in &mut self.entries {
+            self.tick += 1;
+            entry.deadline = deadline(entry, ttl, self.tick);
+            if let Some(deadline) = entry.deadline {
+                self.deadlines.insert(deadline, key.clone());
+            }
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.policy.capacity()
+    }
+
+    // Live entries only, like iter. Expired ones stay stored until looked up, purged or
+    // pushed out by an insert, and only those are counted one by one
+    pub fn len(&self) -> usize {
+        let now = self.clock.now();
+        self.entries.len() - self.deadlines.range(..=(now, u64::MAX)).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        let now = self.clock.now();
+        self.entries.get(key).is_some_and(|entry| !self.is_expired(entry, now))
+    }
+
+    // Looks a value up and counts it as a use, expired entries are dropped
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let now = self.clock.now();
+        if self.entries.get(key).is_some_and(|entry| self.is_expired(entry, now)) {
+            self.remove(key);
+            self.stats.expirations += 1;
+            self.stats.misses += 1;
+            return None;
+        }
+
+        self.tick += 1;
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => {
+                self.stats.misses += 1;
+                return None;
+            }
+        };
+        self.stats.hits += 1;
+        entry.hits += 1;
+        if self.refresh_on_access {
+            entry.stored_at = now;
+            if let Some(old) = entry.deadline {
+                let key = self.deadlines.remove(&old).unwrap();
+                entry.deadline = deadline(entry, self.ttl, self.tick);
+                self.deadlines.insert(entry.deadline.unwrap(), key);
+            }
+        }
+
+        let rank = match self.policy {
+            CachePolicy::Lru(_) => (0, self.tick),
+            CachePolicy::Lfu(_) => (entry.hits, self.tick),
+            CachePolicy::Unbounded | CachePolicy::Fifo(_) => entry.rank,
+        };
+        if rank != entry.rank {
+            let key = self.order.remove(&entry.rank).unwrap();
+            self.order.insert(rank, key);
+            entry.rank = rank;
+        }
+        Some(entry.value.clone())
+    }
+
+    // Stores a value, returns how many entries were evicted to make room
+    pub fn insert(&mut self, key: K, value: V) -> usize {
+        self.insert_entry(key, value, None)
+    }
+
+    // Like insert, but the entry expires after `ttl` even if the cache has no TTL or a
+    // longer one
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> usize {
+        self.insert_entry(key, value, Some(ttl))
+    }
+
+    // Drops every expired entry, returns how many were dropped
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut purged = 0;
+        while let Some(entry) = self.deadlines.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let key = entry.remove();
+            self.remove(&key);
+            purged += 1;
+        }
+        self.stats.expirations += purged as u64;
+        purged
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            size: self.len(),
+            capacity: self.capacity(),
+            ..self.stats
+        }
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    // Live entries in no particular order, expired ones are skipped
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let now = self.clock.now();
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| !self.is_expired(entry, now))
+            .map(|(key, entry)| (key, &entry.value))
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.rank);
+        if let Some(deadline) = entry.deadline {
+            self.deadlines.remove(&deadline);
+        }
+        Some(entry.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.deadlines.clear();
+    }
+
+    fn insert_entry(&mut self, key: K, value: V, ttl: Option<Duration>) -> usize {
+        if self.capacity() == Some(0) {
+            return 0;
+        }
+        self.remove(&key);
+
+        let mut evicted = 0;
+        if let Some(capacity) = self.capacity() {
+            // Expired entries make room before any live one is evicted
+            if self.entries.len() >= capacity {
+                self.purge_expired();
+            }
+            while self.entries.len() >= capacity {
+                let (_, victim) = self.order.pop_first().unwrap();
+                if let Some(deadline) = self.entries.remove(&victim).and_then(|entry| entry.deadline) {
+                    self.deadlines.remove(&deadline);
+                }
+                evicted += 1;
+                self.stats.evictions += 1;
+            }
+        }
+
+        self.tick += 1;
+        let rank = (0, self.tick);
+        let stored_at = self.clock.now();
+        let mut entry = Entry { value, rank, hits: 0, stored_at, ttl, deadline: None };
+        entry.deadline = deadline(&entry, self.ttl, self.tick);
+        if let Some(deadline) = entry.deadline {
+            self.deadlines.insert(deadline, key.clone());
+        }
+        self.order.insert(rank, key.clone());
+        self.entries.insert(key, entry);
+        evicted
+    }
+
+    // A clock that went backwards never expires anything
+    fn is_expired(&self, entry: &Entry<V>, now: SystemTime) -> bool {
+        entry.deadline.is_some_and(|(at, _)| now >= at)
+    }
+}
+
+// When an entry stored under a cache TTL of `shared` expires. `tick` keeps entries
+// expiring at the same time apart, and a deadline past what SystemTime holds never comes
+fn deadline<V>(entry: &Entry<V>, shared: Option<Duration>, tick: u64) -> Option<Deadline> {
+    let ttl = match (entry.ttl, shared) {
+        (Some(own), Some(shared)) => Some(own.min(shared)),
+        (own, shared) => own.or(shared),
+    };
+    let at = entry.stored_at.checked_add(ttl?)?;
+    Some((at, tick))
+}
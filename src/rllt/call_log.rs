@@ -0,0 +1,118 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::sync_functor::lock;
+
+// How much of each call a functor logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    // Inputs and cache status
+    Args,
+    // Inputs, cache status, result and duration
+    Full,
+}
+
+// Where a call's result came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    // Found in the disk cache
+    DiskHit,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::DiskHit => "disk-hit",
+            CacheStatus::Miss => "miss",
+        }
+    }
+}
+
+// One logged call. `result` and `duration` are only filled in at LogLevel::Full
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRecord {
+    pub functor: String,
+    pub args: String,
+    pub kwargs: String,
+    pub cache: CacheStatus,
+    pub result: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl fmt::Display for CallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(args={}, kwargs={}) cache={}", self.functor, self.args, self.kwargs, self.cache.as_str())?;
+        if let Some(result) = &self.result {
+            write!(f, " -> {}", result)?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " in {:?}", duration)?;
+        }
+        Ok(())
+    }
+}
+
+// Destination of call records. Sinks swallow their own failures, logging never fails a call
+pub trait LogSink: Send + Sync {
+    fn write(&self, record: &CallRecord);
+}
+
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&self, record: &CallRecord) {
+        println!("{}", record);
+    }
+}
+
+// Appends one line per call
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink { file: Mutex::new(file) })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, record: &CallRecord) {
+        let _ = writeln!(lock(&self.file), "{}", record);
+    }
+}
+
+// Keeps the records, mainly for tests
+#[derive(Default)]
+pub struct MemorySink {
+    records: Mutex<Vec<CallRecord>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(&self) -> Vec<CallRecord> {
+        lock(&self.records).clone()
+    }
+
+    pub fn clear(&self) {
+        lock(&self.records).clear();
+    }
+}
+
+impl LogSink for MemorySink {
+    fn write(&self, record: &CallRecord) {
+        lock(&self.records).push(record.clone());
+    }
+}
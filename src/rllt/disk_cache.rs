@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use super::json::Json;
+
+enum Layout {
+    // One file per entry, named after the hash of its key
+    Directory,
+    // Every entry is a line of one JSON-lines file, read once when the cache is opened
+    File(Mutex<Lines>),
+}
+
+// Contents of a JSON-lines cache file
+#[derive(Default)]
+struct Lines {
+    entries: HashMap<String, Json>,
+    // Lines of other versions, or that don't parse, kept as they are when the file is rewritten
+    foreign: Vec<String>,
+}
+
+// Memo table kept on disk so it survives process restarts. Keys and values are JSON, keys
+// are compared by their compact text. Entries written under another version are ignored,
+// so bumping the version invalidates everything an older function computed. Each cache
+// location is meant for a single function
+pub struct DiskCache {
+    path: PathBuf,
+    version: String,
+    layout: Layout,
+}
+
+impl DiskCache {
+    pub fn directory(path: impl AsRef<Path>, version: &str) -> io::Result<Self> {
+        fs::create_dir_all(path.as_ref())?;
+        Ok(DiskCache {
+            path: path.as_ref().to_path_buf(),
+            version: version.to_string(),
+            layout: Layout::Directory,
+        })
+    }
+
+    // Lines left by other versions, or that no longer parse, stay in the file untouched
+    pub fn file(path: impl AsRef<Path>, version: &str) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut lines = Lines::default();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // Later lines overwrite earlier ones for the same key
+                    match parse_record(&line, version) {
+                        Some((key, value)) => {
+                            lines.entries.insert(key, value);
+                        }
+                        None => lines.foreign.push(line),
+                    }
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        Ok(DiskCache {
+            path,
+            version: version.to_string(),
+            layout: Layout::File(Mutex::new(lines)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    // Unreadable or foreign entries are treated as missing
+    pub fn get(&self, key: &Json) -> Option<Json> {
+        let key = key.to_string();
+        match &self.layout {
+            Layout::Directory => {
+                let text = fs::read_to_string(self.entry_path(&key)).ok()?;
+                let (stored, value) = parse_record(&text, &self.version)?;
+                (stored == key).then_some(value)
+            }
+            Layout::File(lines) => lock(lines).entries.get(&key).cloned(),
+        }
+    }
+
+    // Two keys with the same hash share a file in a directory cache, the later one wins
+    pub fn insert(&self, key: &Json, value: Json) -> io::Result<()> {
+        let record = self.record(key, &value);
+        let key = key.to_string();
+        match &self.layout {
+            Layout::Directory => write_atomically(&self.entry_path(&key), &record),
+            Layout::File(lines) => {
+                let mut lines = lock(lines);
+                let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                writeln!(file, "{}", record)?;
+                lines.entries.insert(key, value);
+                Ok(())
+            }
+        }
+    }
+
+    // Returns whether there was an entry for the key
+    pub fn remove(&self, key: &Json) -> io::Result<bool> {
+        match &self.layout {
+            Layout::Directory => {
+                if self.get(key).is_none() {
+                    return Ok(false);
+                }
+                fs::remove_file(self.entry_path(&key.to_string()))?;
+                Ok(true)
+            }
+            Layout::File(lines) => {
+                let mut lines = lock(lines);
+                if lines.entries.remove(&key.to_string()).is_none() {
+                    return Ok(false);
+                }
+                self.rewrite(&lines)?;
+                Ok(true)
+            }
+        }
+    }
+
+    // Entries readable under the current version
+    pub fn len(&self) -> usize {
+        match &self.layout {
+            Layout::Directory => self
+                .entry_files()
+                .filter(|path| {
+                    fs::read_to_string(path).is_ok_and(|text| parse_record(&text, &self.version).is_some())
+                })
+                .count(),
+            Layout::File(lines) => lock(lines).entries.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Drops every entry, whatever its version. A directory cache only deletes the files it
+    // names itself, so other files in the directory are left alone
+    pub fn clear(&self) -> io::Result<()> {
+        match &self.layout {
+            Layout::Directory => {
+                for path in self.entry_files() {
+                    fs::remove_file(path)?;
+                }
+                Ok(())
+            }
+            Layout::File(lines) => {
+                let mut lines = lock(lines);
+                *lines = Lines::default();
+                self.rewrite(&lines)
+            }
+        }
+    }
+
+    fn record(&self, key: &Json, value: &Json) -> Json {
+        let mut record = BTreeMap::new();
+        record.insert("key".to_string(), key.clone());
+        record.insert("value".to_string(), value.clone());
+        record.insert("version".to_string(), Json::String(self.version.clone()));
+        Json::Object(record)
+    }
+
+    fn rewrite(&self, lines: &Lines) -> io::Result<()> {
+        let mut text = String::new();
+        for line in &lines.foreign {
+            text.push_str(line);
+            text.push('\n');
+        }
+        for (key, value) in &lines.entries {
+            // Keys were produced by Json's Display, so they parse back
+            let key = Json::parse(key).unwrap();
+            text.push_str(&self.record(&key, value).to_string());
+            text.push('\n');
+        }
+        write_atomically(&self.path, &text)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+
+    // Files named like entry_path names them, whatever version wrote them
+    fn entry_files(&self) -> impl Iterator<Item = PathBuf> {
+        fs::read_dir(&self.path)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(is_entry_name))
+    }
+}
+
+// `{:016x}.json`, as written by entry_path
+fn is_entry_name(name: &str) -> bool {
+    name.strip_suffix(".json")
+        .is_some_and(|hash| hash.len() == 16 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+}
+
+// Key text and value of a record written under `version`
+fn parse_record(text: &str, version: &str) -> Option<(String, Json)> {
+    let record = Json::parse(text).ok()?;
+    if record.get("version")?.as_str()? != version {
+        return None;
+    }
+    Some((record.get("key")?.to_string(), record.get("value")?.clone()))
+}
+
+// Readers never see a half written file. Each write gets its own temporary file, so
+// threads writing the same entry never rename each other's half written data
+fn write_atomically(path: &Path, contents: &impl ToString) -> io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    fs::write(&temporary, contents.to_string())?;
+    fs::rename(&temporary, path)
+}
+
+// 64-bit FNV-1a, stable across processes and compiler versions unlike DefaultHasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+// Writers never leave the map half updated
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -1,60 +1,351 @@
-use std::collections::HashMap;
-use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::any::{type_name, Any};
 use std::boxed::Box;
 use std::fmt::{self, Debug};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::cache::CacheStats;
+use super::functor::{self, FunctorCache, KwargsKey};
+use super::metrics::{self, CallbackMetrics, CallbackStats};
+use super::sync_functor::{lock, SyncF};
+use super::timeit::Timeit;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FabricError {
+    // Names along the cycle, first name repeated at the end
+    Cycle(Vec<String>),
+    MissingDependency { callback: String, dependency: String },
+}
+
+impl fmt::Display for FabricError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FabricError::Cycle(cycle) => write!(f, "dependency cycle: {}", cycle.join(" -> ")),
+            FabricError::MissingDependency { callback, dependency } => {
+                write!(f, "callback '{}' depends on unknown callback '{}'", callback, dependency)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FabricError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackKind {
+    Void,
+    WithArgs,
+}
+
+impl CallbackKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallbackKind::Void => "void",
+            CallbackKind::WithArgs => "with_args",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "void" => Some(CallbackKind::Void),
+            "with_args" => Some(CallbackKind::WithArgs),
+            _ => None,
+        }
+    }
+}
+
+// Registration details of a callback, as reported in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackInfo {
+    pub kind: CallbackKind,
+    pub arg_type: Option<&'static str>,
+    pub return_type: Option<&'static str>,
+    pub description: Option<String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+type ArgsCallback = Box<dyn Fn(&[Box<dyn Any>]) -> Box<dyn Any> + Send + Sync + 'static>;
+
 pub struct Fabric {
-    callbacks_void: HashMap<String, Box<dyn Fn() + 'static>>,
-    callbacks_with_args: HashMap<String, Box<dyn Fn(&[Box<dyn Any>]) -> Box<dyn Any> + 'static>>,
+    callbacks_void: HashMap<String, Box<dyn Fn() + Send + Sync + 'static>>,
+    callbacks_with_args: HashMap<String, ArgsCallback>,
+    dependencies: HashMap<String, Vec<String>>,
+    info: HashMap<String, CallbackInfo>,
+    metrics: Mutex<HashMap<String, CallbackMetrics>>,
+    // Caches of the callbacks registered with add_functor
+    functors: HashMap<String, Arc<dyn FunctorCache>>,
 }
 impl Debug for Fabric {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Fabric")
             .field("callbacks_void", &self.callbacks_void.keys().collect::<Vec<_>>())
             .field("callbacks_with_args", &self.callbacks_with_args.keys().collect::<Vec<_>>())
+            .field("dependencies", &self.dependencies)
+            .field("functors", &self.functors.keys().collect::<Vec<_>>())
             .finish()
     }
 }
+impl Default for Fabric {
+    fn default() -> Self {
+        Fabric::new()
+    }
+}
+
 impl Fabric {
     pub fn new() -> Self {
         Fabric {
             callbacks_void: HashMap::new(),
             callbacks_with_args: HashMap::new(),
+            dependencies: HashMap::new(),
+            info: HashMap::new(),
+            metrics: Mutex::new(HashMap::new()),
+            functors: HashMap::new(),
         }
     }
 
     pub fn add_callback<F>(&mut self, name: String, callback: F)
     where
-        F: Fn() + 'static,
+        F: Fn() + Send + Sync + 'static,
     {
+        self.set_info(&name, CallbackKind::Void, None, None);
+        self.functors.remove(&name);
         self.callbacks_void.insert(name, Box::new(callback));
     }
 
+    // Registers a void callback that `execute` only runs once everything in `after` has run
+    pub fn add_callback_after<F>(&mut self, name: String, after: &[&str], callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.set_dependencies(&name, after);
+        self.add_callback(name, callback);
+    }
+
+    pub fn set_dependencies(&mut self, name: &str, after: &[&str]) {
+        if after.is_empty() {
+            self.dependencies.remove(name);
+        } else {
+            self.dependencies
+                .insert(name.to_string(), after.iter().map(|dep| dep.to_string()).collect());
+        }
+    }
+
     pub fn add_callback_with_args<F, R, A>(&mut self, name: String, callback: F)
     where
-        F: Fn(&A) -> R + 'static,
+        F: Fn(&A) -> R + Send + Sync + 'static,
         R: 'static,
         A: 'static + Debug,
     {
+        self.set_info(&name, CallbackKind::WithArgs, Some(type_name::<A>()), Some(type_name::<R>()));
+        self.functors.remove(&name);
         self.callbacks_with_args.insert(name, Box::new(move |args| {
             let arg = args[0].downcast_ref::<A>().expect("Failed to downcast argument");
             Box::new(callback(arg))
         }));
     }
 
+    // Registers a memoized functor as a with-args callback taking `(args, kwargs)` and
+    // returning `R`, so execute_callback_with_args goes through its cache. The functor sits
+    // behind a Mutex held for the whole call, so parallel callers take turns; see
+    // add_sync_functor. The returned handle shares the functor with the Fabric
+    pub fn add_functor<Func, Args, Kwargs, R>(
+        &mut self,
+        name: String,
+        functor: functor::F<Func, Args, Kwargs, R>,
+    ) -> Arc<Mutex<functor::F<Func, Args, Kwargs, R>>>
+    where
+        Func: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Send + 'static,
+        Args: Clone + PartialEq + Debug + Send + 'static,
+        Kwargs: Clone + PartialEq + Debug + Send + 'static,
+        R: Clone + Send + 'static,
+    {
+        let functor = Arc::new(Mutex::new(functor));
+        let shared = functor.clone();
+        self.add_callback_with_args(
+            name.clone(),
+            move |(args, kwargs): &(Vec<Args>, HashMap<KwargsKey, Kwargs>)| -> R {
+                lock(&shared).call(args.clone(), kwargs.clone())
+            },
+        );
+        self.functors.insert(name, functor.clone());
+        functor
+    }
+
+    // Like add_functor for a SyncF, which is called through &self, so parallel callers
+    // only wait on each other for the same key
+    pub fn add_sync_functor<Func, Args, Kwargs, R>(
+        &mut self,
+        name: String,
+        functor: SyncF<Func, Args, Kwargs, R>,
+    ) -> Arc<SyncF<Func, Args, Kwargs, R>>
+    where
+        Func: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Send + Sync + 'static,
+        Args: Clone + PartialEq + Debug + Send + Sync + 'static,
+        Kwargs: Clone + PartialEq + Debug + Send + Sync + 'static,
+        R: Clone + Send + 'static,
+    {
+        let functor = Arc::new(functor);
+        let shared = functor.clone();
+        self.add_callback_with_args(
+            name.clone(),
+            move |(args, kwargs): &(Vec<Args>, HashMap<KwargsKey, Kwargs>)| -> R {
+                shared.call(args.clone(), kwargs.clone())
+            },
+        );
+        self.functors.insert(name, functor.clone());
+        functor
+    }
+
+    // Clears the in-memory cache of a functor registered with add_functor or
+    // add_sync_functor, returns whether `name` is one. A disk cache the functor persists
+    // to is left alone, like F::clear_cache does
+    pub fn invalidate_functor(&self, name: &str) -> bool {
+        match self.functors.get(name) {
+            Some(functor) => {
+                functor.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn functor_cache_stats(&self, name: &str) -> Option<CacheStats> {
+        self.functors.get(name).map(|functor| functor.stats())
+    }
+
     pub fn remove_callback(&mut self, name: &str) {
         self.callbacks_void.remove(name);
         self.callbacks_with_args.remove(name);
+        self.dependencies.remove(name);
+        self.info.remove(name);
+        self.functors.remove(name);
+        self.metrics.get_mut().unwrap().remove(name);
     }
 
-    pub fn execute(&self) {
-        for callback in self.callbacks_void.values() {
-            callback();
+    pub fn describe(&mut self, name: &str, description: &str) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.description = Some(description.to_string());
         }
     }
 
-    pub fn execute_callback(&self, name: &str) {
-        if let Some(callback) = self.callbacks_void.get(name) {
-            callback();
+    pub fn tag(&mut self, name: &str, key: &str, value: &str) {
+        if let Some(info) = self.info.get_mut(name) {
+            info.tags.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    pub fn info(&self, name: &str) -> Option<&CallbackInfo> {
+        self.info.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.info.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    // Runs every void callback, dependencies first
+    pub fn execute(&self) -> Result<(), FabricError> {
+        for name in self.execution_order()? {
+            self.invoke(&name, &self.callbacks_void[&name]);
+        }
+        Ok(())
+    }
+
+    // Like `execute`, but callbacks whose dependencies are done run concurrently
+    pub fn execute_parallel(&self) -> Result<(), FabricError> {
+        let order = self.execution_order()?;
+        let (mut waiting, dependents) = self.dependency_graph();
+
+        thread::scope(|scope| {
+            let (done, finished) = mpsc::channel();
+            let mut ready: Vec<&str> = order
+                .iter()
+                .map(String::as_str)
+                .filter(|name| waiting[name] == 0)
+                .collect();
+            let mut running = 0;
+
+            loop {
+                for name in ready.drain(..) {
+                    let callback = &self.callbacks_void[name];
+                    let guard = Finished { name, done: done.clone() };
+                    scope.spawn(move || {
+                        let _guard = guard;
+                        self.invoke(name, callback);
+                    });
+                    running += 1;
+                }
+                if running == 0 {
+                    break;
+                }
+
+                let (name, completed) = finished.recv().expect("Callback result channel closed");
+                running -= 1;
+                if !completed {
+                    // A callback panicked, let the scope propagate it once the rest finish
+                    continue;
+                }
+                for dependent in dependents.get(name).into_iter().flatten() {
+                    let count = waiting.get_mut(dependent).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(dependent);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // Topological order of the void callbacks, ties broken by name
+    pub fn execution_order(&self) -> Result<Vec<String>, FabricError> {
+        for (name, deps) in &self.dependencies {
+            if !self.callbacks_void.contains_key(name) {
+                continue;
+            }
+            if let Some(dep) = deps.iter().find(|dep| !self.callbacks_void.contains_key(*dep)) {
+                return Err(FabricError::MissingDependency {
+                    callback: name.clone(),
+                    dependency: dep.clone(),
+                });
+            }
+        }
+
+        let (mut waiting, dependents) = self.dependency_graph();
+        let mut ready: BTreeSet<&str> = waiting
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(name, _)| *name)
+            .collect();
+        let mut order = Vec::with_capacity(waiting.len());
+        while let Some(name) = ready.pop_first() {
+            order.push(name.to_string());
+            for dependent in dependents.get(name).into_iter().flatten() {
+                let count = waiting.get_mut(dependent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        if order.len() < waiting.len() {
+            return Err(FabricError::Cycle(self.find_cycle(&waiting)));
+        }
+        Ok(order)
+    }
+
+    // Returns whether `name` is a registered void callback
+    pub fn execute_callback(&self, name: &str) -> bool {
+        match self.callbacks_void.get(name) {
+            Some(callback) => {
+                self.invoke(name, callback);
+                true
+            }
+            None => false,
         }
     }
 
@@ -64,9 +355,126 @@ impl Fabric {
         A: 'static + Debug,
     {
         if let Some(callback) = self.callbacks_with_args.get(name) {
-            let result = callback(&[Box::new(arg)]);
-            return result.downcast::<R>().ok().map(|r| *r);
+            let result = self.invoke(name, || callback(&[Box::new(arg)]));
+            let result = result.downcast::<R>().ok().map(|r| *r);
+            if result.is_none() {
+                self.with_metrics(name, CallbackMetrics::record_mismatch);
+            }
+            return result;
         }
         None
     }
-}
\ No newline at end of file
+
+    // Invocation metrics for a registered callback
+    pub fn stats(&self, name: &str) -> Option<CallbackStats> {
+        if let Some(metrics) = self.metrics.lock().unwrap().get(name) {
+            return Some(metrics.stats());
+        }
+        if self.callbacks_void.contains_key(name) || self.callbacks_with_args.contains_key(name) {
+            return Some(CallbackStats::default());
+        }
+        None
+    }
+
+    pub fn reset_stats(&self) {
+        self.metrics.lock().unwrap().clear();
+    }
+
+    // Metrics of every callback invoked so far in Prometheus text format
+    pub fn stats_prometheus(&self) -> String {
+        let stats: BTreeMap<String, CallbackStats> = self
+            .metrics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, metrics)| (name.clone(), metrics.stats()))
+            .collect();
+        metrics::prometheus(&stats)
+    }
+
+    // Times a callback and counts panics without swallowing them
+    fn invoke<T>(&self, name: &str, callback: impl FnOnce() -> T) -> T {
+        let mut timer = Timeit::new();
+        let start = timer.__enter();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(callback));
+        timer.__exit(start);
+
+        let elapsed = timer.elapsed();
+        self.with_metrics(name, |metrics| metrics.record(elapsed, outcome.is_err()));
+        match outcome {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn with_metrics(&self, name: &str, update: impl FnOnce(&mut CallbackMetrics)) {
+        let mut metrics = self.metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        update(metrics.entry(name.to_string()).or_default());
+    }
+
+    // Re-registering under the same name keeps the description and tags
+    fn set_info(&mut self, name: &str, kind: CallbackKind, arg_type: Option<&'static str>, return_type: Option<&'static str>) {
+        let info = self.info.entry(name.to_string()).or_insert_with(|| CallbackInfo {
+            kind,
+            arg_type: None,
+            return_type: None,
+            description: None,
+            tags: BTreeMap::new(),
+        });
+        info.kind = kind;
+        info.arg_type = arg_type;
+        info.return_type = return_type;
+    }
+
+    // Number of unmet dependencies per void callback, and who waits on each callback
+    fn dependency_graph(&self) -> (HashMap<&str, usize>, HashMap<&str, Vec<&str>>) {
+        let mut waiting: HashMap<&str, usize> = HashMap::new();
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for name in self.callbacks_void.keys() {
+            let deps = self.dependencies.get(name).map_or(&[][..], |deps| &deps[..]);
+            waiting.insert(name, deps.len());
+            for dep in deps {
+                dependents.entry(dep.as_str()).or_default().push(name);
+            }
+        }
+        (waiting, dependents)
+    }
+
+    // Walks dependency edges among callbacks left unsorted until a name repeats
+    fn find_cycle(&self, waiting: &HashMap<&str, usize>) -> Vec<String> {
+        let mut current = waiting
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(name, _)| *name)
+            .min()
+            .expect("No unsorted callbacks");
+        let mut path: Vec<&str> = Vec::new();
+
+        loop {
+            if let Some(start) = path.iter().position(|name| *name == current) {
+                let mut cycle: Vec<String> = path[start..].iter().map(|name| name.to_string()).collect();
+                cycle.push(current.to_string());
+                return cycle;
+            }
+            path.push(current);
+            current = self.dependencies[current]
+                .iter()
+                .map(String::as_str)
+                .filter(|dep| waiting.get(dep).is_some_and(|count| *count > 0))
+                .min()
+                .expect("Unsorted callback without unsorted dependency");
+        }
+    }
+}
+
+// Reports a finished callback to `execute_parallel`, even when it panicked
+struct Finished<'a> {
+    name: &'a str,
+    done: Sender<(&'a str, bool)>,
+}
+
+impl Drop for Finished<'_> {
+    fn drop(&mut self) {
+        let _ = self.done.send((self.name, !thread::panicking()));
+    }
+}
@@ -1,144 +1,1290 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::hash::{Hash, Hasher};
 use std::fmt;
+use std::marker::PhantomData;
 use std::ops::Fn;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+use super::cache::{Cache, CachePolicy, CacheStats, ErrorCaching};
+use super::call_log::{CacheStatus, CallRecord, LogLevel, LogSink};
+use super::clock::Clock;
+use super::disk_cache::DiskCache;
+use super::json::{FromJson, Json, ToJson};
+use super::signature::{Signature, SignatureError};
+use super::snapshot::{self, Row, SnapshotError};
+use super::timeit::Timeit;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-struct CacheKey(Vec<i32>, HashMap<String, String>);
 
-// Functor struct
-pub(crate) struct F<Functor, Args, Kwargs> {
+// Cache key holding the actual arguments, kwargs sorted by name so their order does not matter.
+// Hashing uses the precomputed key hash, equality compares the arguments themselves
+#[derive(Debug, Clone)]
+pub(crate) struct CacheKey<Args, Kwargs> {
+    hash: u64,
+    args: Vec<Args>,
+    kwargs: Vec<(KwargsKey, Kwargs)>,
+}
+
+impl<Args, Kwargs> CacheKey<Args, Kwargs> {
+    pub(crate) fn new(args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>, key_fn: &KeyFn<Args, Kwargs>) -> Self {
+        let mut kwargs: Vec<(KwargsKey, Kwargs)> = kwargs.into_iter().collect();
+        kwargs.sort_by(|a, b| a.0.cmp(&b.0));
+        let hash = key_fn(&args, &kwargs);
+        CacheKey { hash, args, kwargs }
+    }
+
+    pub(crate) fn hash_value(&self) -> u64 {
+        self.hash
+    }
+
+    pub(crate) fn args(&self) -> &[Args] {
+        &self.args
+    }
+
+    pub(crate) fn kwargs(&self) -> &[(KwargsKey, Kwargs)] {
+        &self.kwargs
+    }
+}
+
+impl<Args: PartialEq, Kwargs: PartialEq> PartialEq for CacheKey<Args, Kwargs> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.args == other.args && self.kwargs == other.kwargs
+    }
+}
+
+impl<Args: PartialEq, Kwargs: PartialEq> Eq for CacheKey<Args, Kwargs> {}
+
+impl<Args, Kwargs> Hash for CacheKey<Args, Kwargs> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+// Functor struct. `Check` is Unchecked until a signature is declared, see Checking
+pub struct F<Functor, Args, Kwargs, R, Check = Unchecked> {
     func: Functor,
-    args: Args,
-    kwargs: Kwargs,
-    cache: HashMap<CacheKey, Result>,
+    args: Vec<Args>,
+    kwargs: HashMap<KwargsKey, Kwargs>,
+    key_fn: KeyFn<Args, Kwargs>,
+    pub(crate) cache: Cache<CacheKey<Args, Kwargs>, R>,
+    disk: Option<Persist<Args, Kwargs, R>>,
+    validation: Vec<Validation<Kwargs>>,
+    errors: Option<ErrorPolicy<R>>,
+    name: Option<String>,
+    logging: Option<Logging<R>>,
+    checking: PhantomData<Check>,
+}
+
+// Functors without a signature, their calls can't be rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unchecked;
+
+// Functors with a signature, see F::signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checked;
+
+// Whether a functor's calls are checked against a signature, and so what they return:
+// the result itself when Unchecked, Result<R, SignatureError> when Checked
+pub trait Checking {
+    type Error;
+    type Output<T>;
+
+    // Runs `check` on a call's bound inputs, Unchecked functors have nothing to check
+    fn check<Args, Kwargs>(
+        inputs: Inputs<Args, Kwargs>,
+        check: impl FnOnce(&mut Inputs<Args, Kwargs>) -> Result<(), SignatureError>,
+    ) -> Result<Inputs<Args, Kwargs>, Self::Error>;
+
+    fn output<T>(result: Result<T, Self::Error>) -> Self::Output<T>;
+
+    fn error_into<E: From<SignatureError>>(error: Self::Error) -> E;
+}
+
+impl Checking for Unchecked {
+    type Error = Infallible;
+    type Output<T> = T;
+
+    fn check<Args, Kwargs>(
+        inputs: Inputs<Args, Kwargs>,
+        _: impl FnOnce(&mut Inputs<Args, Kwargs>) -> Result<(), SignatureError>,
+    ) -> Result<Inputs<Args, Kwargs>, Infallible> {
+        Ok(inputs)
+    }
+
+    fn output<T>(result: Result<T, Infallible>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    fn error_into<E: From<SignatureError>>(never: Infallible) -> E {
+        match never {}
+    }
+}
+
+impl Checking for Checked {
+    type Error = SignatureError;
+    type Output<T> = Result<T, SignatureError>;
+
+    fn check<Args, Kwargs>(
+        mut inputs: Inputs<Args, Kwargs>,
+        check: impl FnOnce(&mut Inputs<Args, Kwargs>) -> Result<(), SignatureError>,
+    ) -> Result<Inputs<Args, Kwargs>, SignatureError> {
+        check(&mut inputs)?;
+        Ok(inputs)
+    }
+
+    fn output<T>(result: Result<T, SignatureError>) -> Result<T, SignatureError> {
+        result
+    }
+
+    fn error_into<E: From<SignatureError>>(error: SignatureError) -> E {
+        error.into()
+    }
+}
+
+// Typestate of a pipeline, Checked when any of its stages is
+pub trait Join<Other> {
+    type Output: Checking;
+}
+
+impl Join<Unchecked> for Unchecked {
+    type Output = Unchecked;
+}
+
+impl Join<Checked> for Unchecked {
+    type Output = Checked;
+}
+
+impl<Other> Join<Other> for Checked {
+    type Output = Checked;
+}
+
+// Where and how much of every call is logged
+#[derive(Clone)]
+struct Logging<R> {
+    level: LogLevel,
+    sink: Arc<dyn LogSink>,
+    format_result: fn(&R) -> String,
+}
+
+// How a fallible functor caches its Err results
+#[derive(Clone)]
+struct ErrorPolicy<R> {
+    caching: ErrorCaching,
+    is_error: fn(&R) -> bool,
+}
+
+type ValidateFn<Kwargs> = fn(&Signature, usize, &mut HashMap<KwargsKey, Kwargs>) -> Result<(), SignatureError>;
+type DefaultsFn<Kwargs> = fn(&Signature, &mut HashMap<KwargsKey, Kwargs>);
+
+// Declared signature of a functor. A composed pipeline checks its inputs against the
+// signatures of all its stages before any of them runs, each with the inputs that stage
+// will see
+#[derive(Clone)]
+struct Validation<Kwargs> {
+    signature: Signature,
+    validate: ValidateFn<Kwargs>,
+    defaults: DefaultsFn<Kwargs>,
+    stage: Option<StageInputs<Kwargs>>,
+}
+
+// What a pipeline stage is called with besides the pipeline's kwargs
+#[derive(Clone)]
+struct StageInputs<Kwargs> {
+    args: StageArgs,
+    // Bound by the stage and the functors wrapping it, the pipeline's kwargs override them
+    kwargs: HashMap<KwargsKey, Kwargs>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StageArgs {
+    // The pipeline's args after this many bound ones
+    After(usize),
+    // Stages after the first get the previous result, whatever the pipeline was called with
+    Fixed(usize),
+}
+
+impl<Kwargs: Clone> Validation<Kwargs> {
+    // This check as a stage of a pipeline, for a functor that binds `bound_args` and
+    // `bound_kwargs`. `passed` is how many args the pipeline passes it, None for its own
+    fn staged(&self, bound_args: usize, passed: Option<usize>, bound_kwargs: &HashMap<KwargsKey, Kwargs>) -> Self {
+        let (args, mut kwargs) = match &self.stage {
+            Some(stage) => (stage.args, stage.kwargs.clone()),
+            None => (StageArgs::After(0), HashMap::new()),
+        };
+        let args = match (args, passed) {
+            (StageArgs::After(offset), None) => StageArgs::After(offset + bound_args),
+            (StageArgs::After(offset), Some(passed)) => StageArgs::Fixed(offset + bound_args + passed),
+            (fixed, _) => fixed,
+        };
+        kwargs.extend(bound_kwargs.clone());
+        Validation { stage: Some(StageInputs { args, kwargs }), ..self.clone() }
+    }
+}
+
+type DiskKeyFn<Args, Kwargs> = fn(&[Args], &[(KwargsKey, Kwargs)]) -> Option<Json>;
+
+// Disk cache backing a functor, with the codecs for its argument and result types
+#[derive(Clone)]
+struct Persist<Args, Kwargs, R> {
+    disk: Arc<DiskCache>,
+    key: DiskKeyFn<Args, Kwargs>,
+    encode: fn(&R) -> Json,
+    decode: fn(&Json) -> Option<R>,
 }
 
 // Type definitions for convenience
 pub  type KwargsKey = String;
-pub type KwargsValue = String;
-pub type Result = String;
+pub use super::kwargs::KwargsValue;
+// Hashes the full positional args and the kwargs sorted by name. Keys that hash equal are
+// still compared with PartialEq, so a weak hash only costs lookups, never wrong results.
+// Arguments that are not equal to themselves, like NaN floats, never hit: every such call
+// computes again and adds an entry, so bound the cache (see cache_policy) for those inputs
+pub type KeyFn<Args, Kwargs> = Arc<dyn Fn(&[Args], &[(KwargsKey, Kwargs)]) -> u64 + Send + Sync>;
+
+// Default KeyFn for hashable arguments
+pub fn hash_key<Args: Hash, Kwargs: Hash>(args: &[Args], kwargs: &[(KwargsKey, Kwargs)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    args.hash(&mut hasher);
+    kwargs.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Positional and keyword args of a call
+pub type Inputs<Args, Kwargs> = (Vec<Args>, HashMap<KwargsKey, Kwargs>);
+
+// Function wrapped by a composed functor, clones of it share the pipeline's stages
+pub trait Stage<Args, Kwargs, R>: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Clone {}
+
+impl<T, Args, Kwargs, R> Stage<Args, Kwargs, R> for T where T: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Clone {}
+
+// Stage of a fallible pipeline, and the functor around it
+pub trait TryStage<Args, Kwargs, T, E>: Stage<Args, Kwargs, Result<T, E>> {}
+
+impl<S, Args, Kwargs, T, E> TryStage<Args, Kwargs, T, E> for S where S: Stage<Args, Kwargs, Result<T, E>> {}
+
+pub type TryF<Functor, Args, Kwargs, T, E, Check = Unchecked> = F<Functor, Args, Kwargs, Result<T, E>, Check>;
 
 // Implementing F
-impl<Functor, Args, Kwargs> F<Functor, Args, Kwargs>
+impl<Functor, Args, Kwargs, R> F<Functor, Args, Kwargs, R>
 where
-    Functor: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> Result,
-    Args: Clone + PartialEq + Hash + fmt::Debug,
-    Kwargs: Clone + PartialEq + Hash + fmt::Debug,
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+    Args: Clone + PartialEq + fmt::Debug,
+    Kwargs: Clone + PartialEq + fmt::Debug,
+    R: Clone,
 {
     // Constructor
-    pub fn new(func: Functor, args: Args, kwargs: Kwargs) -> Self {
+    pub fn new(func: Functor, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Self
+    where
+        Args: Hash + 'static,
+        Kwargs: Hash + 'static,
+    {
+        F::with_key_fn(func, args, kwargs, Arc::new(hash_key::<Args, Kwargs>))
+    }
+
+    // Constructor for arguments that are not Hash, or need custom hashing
+    pub fn with_key_fn(
+        func: Functor,
+        args: Vec<Args>,
+        kwargs: HashMap<KwargsKey, Kwargs>,
+        key_fn: KeyFn<Args, Kwargs>,
+    ) -> Self {
         F {
             func,
             args,
             kwargs,
-            cache: HashMap::new(),
+            key_fn,
+            cache: Cache::new(CachePolicy::Unbounded),
+            disk: None,
+            validation: Vec::new(),
+            errors: None,
+            name: None,
+            logging: None,
+            checking: PhantomData,
         }
     }
+}
+
+impl<Functor, Args, Kwargs, R, Check> F<Functor, Args, Kwargs, R, Check>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+    Args: Clone + PartialEq + fmt::Debug,
+    Kwargs: Clone + PartialEq + fmt::Debug,
+    R: Clone,
+    Check: Checking,
+{
+
+    // Selects how the cache evicts entries, any cached results are dropped
+    pub fn cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache.set_policy(policy);
+        self
+    }
 
-    // Call method
-    pub fn call(&mut self, args: Vec<Args>, kwargs: HashMap<KwargsKey, KwargsValue>) -> Result {
-        let cache_key = CacheKey(args.clone(), kwargs.clone());
+    // Cached results older than `ttl` are recomputed
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache.set_ttl(Some(ttl), false);
+        self
+    }
 
-        if let Some(result) = self.cache.get(&cache_key) {
-            result.clone()
-        } else {
-            let result = (self.func)(args.clone(), kwargs.clone());
-            self.cache.insert(cache_key, result.clone());
-            result
+    // Like `cache_ttl`, but every cache hit restarts the entry's lifetime
+    pub fn cache_ttl_refreshing(mut self, ttl: Duration) -> Self {
+        self.cache.set_ttl(Some(ttl), true);
+        self
+    }
+
+    // Time source for cache expiry, mainly for tests
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cache.set_clock(clock);
+        self
+    }
+
+    // Backs the cache with `disk`. Results are looked up there on a miss and written there
+    // when computed, so they survive process restarts
+    pub fn persist(mut self, disk: Arc<DiskCache>) -> Self
+    where
+        Args: ToJson,
+        Kwargs: ToJson,
+        R: ToJson + FromJson,
+    {
+        self.disk = Some(Persist {
+            disk,
+            key: disk_key::<Args, Kwargs>,
+            encode: R::to_json,
+            decode: R::from_json,
+        });
+        self
+    }
+
+    // Name used in call logs instead of the function's type name, and part of the
+    // functor's identity, see PartialEq
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // Logs every call to `sink` in as much detail as `level` asks for
+    pub fn log_calls(mut self, level: LogLevel, sink: Arc<dyn LogSink>) -> Self
+    where
+        R: fmt::Debug,
+    {
+        self.logging = match level {
+            LogLevel::Off => None,
+            level => Some(Logging { level, sink, format_result: |result| format!("{:?}", result) }),
+        };
+        self
+    }
+
+    // Drops expired results without waiting for them to be looked up
+    pub fn purge_expired(&mut self) -> usize {
+        self.cache.purge_expired()
+    }
+
+    pub fn cache_len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // None for an unbounded cache
+    pub fn cache_capacity(&self) -> Option<usize> {
+        self.cache.capacity()
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    pub fn reset_cache_stats(&mut self) {
+        self.cache.reset_stats();
+    }
+
+    // Cached (args, kwargs sorted by name, result) triples, args include the bound ones
+    pub fn cache_entries(&self) -> impl Iterator<Item = (&[Args], &[(KwargsKey, Kwargs)], &R)> {
+        self.cache.iter().map(|(key, result)| (key.args(), key.kwargs(), result))
+    }
+
+    // Caches known results as if they had been computed, e.g. to warm a fresh process.
+    // The inputs are bound and validated like a call's, returns how many were cached
+    pub fn warm<Entries>(&mut self, entries: Entries) -> Check::Output<usize>
+    where
+        Entries: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>, R)>,
+    {
+        let mut warmed = Vec::new();
+        for (args, kwargs, result) in entries {
+            match self.prepare(args, kwargs) {
+                Ok((all_args, all_kwargs)) => warmed.push((CacheKey::new(all_args, all_kwargs, &self.key_fn), result)),
+                Err(error) => return Check::output(Err(error)),
+            }
+        }
+        let count = warmed.len();
+        for (cache_key, result) in warmed {
+            self.remember(cache_key, &result);
+        }
+        Check::output(Ok(count))
+    }
+
+    // Snapshot of the cached results, see import_json. Entries holding NaN or infinite
+    // floats are left out, JSON can only write them as null which would not import
+    pub fn export_json(&self) -> Json
+    where
+        Args: ToJson,
+        Kwargs: ToJson,
+        R: ToJson,
+    {
+        snapshot::write_json(self.snapshot_rows())
+    }
+
+    // Same entries as export_json, one CSV line each
+    pub fn export_csv(&self) -> String
+    where
+        Args: ToJson,
+        Kwargs: ToJson,
+        R: ToJson,
+    {
+        snapshot::write_csv(self.snapshot_rows())
+    }
+
+    // Caches the entries of a snapshot, which hold complete inputs so bound args are not
+    // added again. Nothing is imported if any entry fails to decode
+    pub fn import_json(&mut self, snapshot: &Json) -> Result<usize, SnapshotError>
+    where
+        Args: FromJson,
+        Kwargs: FromJson,
+        R: FromJson,
+    {
+        self.import_rows(snapshot::read_json(snapshot)?)
+    }
+
+    pub fn import_csv(&mut self, text: &str) -> Result<usize, SnapshotError>
+    where
+        Args: FromJson,
+        Kwargs: FromJson,
+        R: FromJson,
+    {
+        self.import_rows(snapshot::read_csv(text)?)
+    }
+
+    // Drops the cached result for one call, also from the disk cache, returns whether
+    // there was one. Inputs the signature rejects were never cached
+    pub fn invalidate(&mut self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> bool {
+        let (all_args, all_kwargs) = match self.prepare(args, kwargs) {
+            Ok(inputs) => inputs,
+            Err(_) => return false,
+        };
+        let cache_key = CacheKey::new(all_args, all_kwargs, &self.key_fn);
+        let on_disk = self.disk.as_ref().is_some_and(|persist| {
+            let key = (persist.key)(cache_key.args(), cache_key.kwargs());
+            key.is_some_and(|key| persist.disk.remove(&key).unwrap_or(false))
+        });
+        self.cache.remove(&cache_key).is_some() || on_disk
+    }
+
+    // Call method, bound args come first and call-time kwargs override bound ones. With a
+    // signature the inputs are validated, and defaults filled in, before the cache lookup
+    pub fn call(&mut self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Check::Output<R> {
+        let result = self.prepare(args, kwargs).map(|(all_args, all_kwargs)| self.run(all_args, all_kwargs));
+        Check::output(result)
+    }
+
+    // Call of a pipeline stage, the pipeline checked its inputs already so only the
+    // defaults are filled in
+    fn call_stage(&mut self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> R {
+        let (all_args, mut all_kwargs) = self.bind(args, kwargs);
+        for validation in self.validation.iter().filter(|validation| validation.stage.is_none()) {
+            (validation.defaults)(&validation.signature, &mut all_kwargs);
         }
+        self.run(all_args, all_kwargs)
     }
 
+    // Call with bound and validated inputs
+    fn run(&mut self, all_args: Vec<Args>, all_kwargs: HashMap<KwargsKey, Kwargs>) -> R {
+        let cache_key = CacheKey::new(all_args.clone(), all_kwargs.clone(), &self.key_fn);
+
+        let mut timer = Timeit::new();
+        let start = timer.__enter();
+        let (result, status) = match self.lookup(&cache_key) {
+            Some(found) => found,
+            None => ((self.func)(all_args, all_kwargs), CacheStatus::Miss),
+        };
+        timer.__exit(start);
+
+        self.log(&cache_key, status, &result, timer.elapsed());
+        if status == CacheStatus::Miss {
+            self.remember(cache_key, &result);
+        }
+        result
+    }
 
-    // Chain method
-    pub fn chain<Transform>(&mut self, other_func: Transform) -> Result
+    // Calls the functor once per input and returns the results in input order. Identical
+    // inputs are computed once, cached ones are not computed at all. Every input is
+    // validated before anything runs
+    pub fn call_many<Calls>(&mut self, inputs: Calls) -> Check::Output<Vec<R>>
     where
-        Transform: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> Result,
+        Calls: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>)>,
     {
-        let result = self.call(self.args.clone(), self.kwargs.clone());
-        F::new(other_func, self.args.clone(), self.kwargs.clone()).call(vec![result], HashMap::new())
+        Check::output(self.batch(inputs, compute_all))
     }
 
-    // Map method
-    pub fn map<Transform>(&mut self, transform_func: Transform) -> Result
+    // Like call_many, but the misses are split over up to `threads` scoped threads
+    pub fn call_many_parallel<Calls>(&mut self, inputs: Calls, threads: usize) -> Check::Output<Vec<R>>
     where
-        Transform: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> Result,
+        Calls: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>)>,
+        Functor: Sync,
+        Args: Send,
+        Kwargs: Send,
+        R: Send,
     {
-        let result = self.call(self.args.clone(), self.kwargs.clone());
-        F::new(transform_func, self.args.clone(), self.kwargs.clone()).call(vec![result], HashMap::new())
+        Check::output(self.batch(inputs, |func, misses| {
+            let threads = threads.max(1);
+            let mut buckets: Vec<Vec<Miss<Args, Kwargs>>> = (0..threads).map(|_| Vec::new()).collect();
+            for (i, miss) in misses.into_iter().enumerate() {
+                buckets[i % threads].push(miss);
+            }
+            thread::scope(|scope| {
+                let workers: Vec<_> = buckets
+                    .into_iter()
+                    .filter(|bucket| !bucket.is_empty())
+                    .map(|bucket| scope.spawn(move || compute_all(func, bucket)))
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                    .collect()
+            })
+        }))
+    }
+
+    // Chain method, lazily composes `other_func` after this functor. The composed functor
+    // passes the result on as the single argument, together with the kwargs the first stage
+    // was called with. Both the pipeline and this stage are memoized
+    pub fn chain<Transform, Output>(
+        &self,
+        other_func: Transform,
+    ) -> F<impl Stage<Args, Kwargs, Output>, Args, Kwargs, Output, Check>
+    where
+        Functor: Clone,
+        Transform: Fn(Vec<R>, HashMap<KwargsKey, Kwargs>) -> Output + Clone,
+        Output: Clone,
+    {
+        let stage = self.stage();
+        let mut composed = F::with_key_fn(
+            move |args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>| {
+                let (result, kwargs) = run_stage(&stage, args, kwargs);
+                other_func(vec![result], kwargs)
+            },
+            Vec::new(),
+            HashMap::new(),
+            self.key_fn.clone(),
+        );
+        composed.validation = self
+            .validation
+            .iter()
+            .map(|validation| validation.staged(self.args.len(), None, &self.kwargs))
+            .collect();
+        composed.into_checking()
+    }
+
+    // Map method, like chain but the transform sees the result alone, without kwargs
+    pub fn map<Transform, Output>(
+        &self,
+        transform_func: Transform,
+    ) -> F<impl Stage<Args, Kwargs, Output>, Args, Kwargs, Output, Check>
+    where
+        Functor: Clone,
+        Transform: Fn(Vec<R>, HashMap<KwargsKey, Kwargs>) -> Output + Clone,
+        Output: Clone,
+    {
+        self.chain(move |results: Vec<R>, _: HashMap<KwargsKey, Kwargs>| transform_func(results, HashMap::new()))
+    }
+
+    // Then method, composes another memoized functor after this one, so every stage keeps
+    // its own cache. A stage can opt out with `cache_policy(CachePolicy::Lru(0))`. The
+    // signatures of both stages are checked before either runs
+    pub fn then<Next, Output, NextCheck>(
+        &self,
+        next: F<Next, R, Kwargs, Output, NextCheck>,
+    ) -> F<impl Stage<Args, Kwargs, Output>, Args, Kwargs, Output, <Check as Join<NextCheck>>::Output>
+    where
+        Functor: Clone,
+        Next: Fn(Vec<R>, HashMap<KwargsKey, Kwargs>) -> Output,
+        R: PartialEq + fmt::Debug,
+        Output: Clone,
+        Check: Join<NextCheck>,
+        NextCheck: Checking,
+    {
+        // `next` is passed this stage's result and the kwargs this stage saw
+        let mut forwarded = next.kwargs.clone();
+        forwarded.extend(self.kwargs.clone());
+        let checks: Vec<_> = next.validation.iter().map(|validation| validation.staged(next.args.len(), Some(1), &forwarded)).collect();
+
+        let next = Arc::new(Mutex::new(next));
+        let mut composed = self.chain(move |results: Vec<R>, kwargs: HashMap<KwargsKey, Kwargs>| {
+            next.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).call_stage(results, kwargs)
+        });
+        composed.validation.extend(checks);
+        composed.into_checking()
     }
 
     // Filter method
-    pub fn filter<Filter>(&mut self, filter_func: Filter) -> Option<Result>
+    pub fn filter<Filter>(&mut self, filter_func: Filter) -> Check::Output<Option<R>>
     where
-        Filter: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> bool,
+        Filter: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> bool,
     {
-        if filter_func(self.args.clone(), self.kwargs.clone()) {
-            Some(self.call(self.args.clone(), self.kwargs.clone()))
-        } else {
-            None
+        if !filter_func(self.args.clone(), self.kwargs.clone()) {
+            return Check::output(Ok(None));
         }
+        let result = self.prepare(Vec::new(), HashMap::new()).map(|(all_args, all_kwargs)| Some(self.run(all_args, all_kwargs)));
+        Check::output(result)
     }
 
-    // Curry method
-    pub fn curry(&mut self, curry_args: Vec<Args>, curry_kwargs: HashMap<KwargsKey, KwargsValue>) -> Self {
+    // Curry method, partial application returning a new functor. Curried args go after the
+    // ones already bound and curried kwargs override bound ones; call-time kwargs override
+    // both. Cached results carry over, their keys hold the complete argument list
+    pub fn curry(&self, curry_args: Vec<Args>, curry_kwargs: HashMap<KwargsKey, Kwargs>) -> Self
+    where
+        Functor: Clone,
+    {
+        let (args, kwargs) = self.bind(curry_args, curry_kwargs);
         F {
             func: self.func.clone(),
-            args: self.args.clone(),
-            kwargs: self.kwargs.clone(),
+            args,
+            kwargs,
+            key_fn: self.key_fn.clone(),
             cache: self.cache.clone(),
+            disk: self.disk.clone(),
+            validation: self.validation.clone(),
+            errors: self.errors.clone(),
+            name: self.name.clone(),
+            logging: self.logging.clone(),
+            checking: PhantomData,
         }
     }
 
     // Transform args method
-    pub fn transform_args<Transform>(&mut self, transform_func: Transform) -> F<Transform, Args, Kwargs>
+    pub fn transform_args<Transform>(&mut self, transform_func: Transform) -> F<Transform, Args, Kwargs, Inputs<Args, Kwargs>>
     where
-        Transform: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> (Vec<Args>, HashMap<KwargsKey, KwargsValue>),
+        Transform: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> Inputs<Args, Kwargs>,
     {
         let (transformed_args, transformed_kwargs) = transform_func(self.args.clone(), self.kwargs.clone());
-        F::new(transform_func, transformed_args, transformed_kwargs)
+        F::with_key_fn(transform_func, transformed_args, transformed_kwargs, self.key_fn.clone())
+            .cache_policy(self.cache.policy())
     }
 
-    // Reduce method
-    pub fn reduce<Reducer>(&mut self, reducer_func: Reducer, initial: Option<Result>) -> Self
+    // Reduce method, calls the functor once per argument set (through the cache) and folds
+    // the results into the accumulator in order. Stops at the first rejected argument set
+    pub fn reduce<Sets, Acc, Reducer>(
+        &mut self,
+        arg_sets: Sets,
+        reducer_func: Reducer,
+        initial: Acc,
+    ) -> Check::Output<Acc>
     where
-        Reducer: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>, Option<Result>) -> Result,
+        Sets: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>)>,
+        Reducer: Fn(Acc, R) -> Acc,
     {
+        let mut acc = initial;
+        for (args, kwargs) in arg_sets {
+            match self.prepare(args, kwargs) {
+                Ok((all_args, all_kwargs)) => acc = reducer_func(acc, self.run(all_args, all_kwargs)),
+                Err(error) => return Check::output(Err(error)),
+            }
+        }
+        Check::output(Ok(acc))
+    }
+
+    // Scan method, like reduce but lazily yields the accumulator after every argument set.
+    // A rejected argument set is yielded as the error and ends the iteration
+    pub fn scan<'a, Sets, Acc, Reducer>(
+        &'a mut self,
+        arg_sets: Sets,
+        reducer_func: Reducer,
+        initial: Acc,
+    ) -> impl Iterator<Item = Check::Output<Acc>> + 'a
+    where
+        Sets: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>)>,
+        Sets::IntoIter: 'a,
+        Reducer: Fn(Acc, R) -> Acc + 'a,
+        Acc: Clone + 'a,
+    {
+        arg_sets.into_iter().scan(Some(initial), move |state, (args, kwargs)| {
+            let acc = state.take()?;
+            match self.prepare(args, kwargs) {
+                Ok((all_args, all_kwargs)) => {
+                    let acc = reducer_func(acc, self.run(all_args, all_kwargs));
+                    *state = Some(acc.clone());
+                    Some(Check::output(Ok(acc)))
+                }
+                Err(error) => Some(Check::output(Err(error))),
+            }
+        })
+    }
+
+    // Clear cache method, a disk cache is left alone, see DiskCache::clear
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    // The same functor in another typestate
+    fn into_checking<Other>(self) -> F<Functor, Args, Kwargs, R, Other> {
         F {
-            func: self.func.clone(),
+            func: self.func,
+            args: self.args,
+            kwargs: self.kwargs,
+            key_fn: self.key_fn,
+            cache: self.cache,
+            disk: self.disk,
+            validation: self.validation,
+            errors: self.errors,
+            name: self.name,
+            logging: self.logging,
+            checking: PhantomData,
+        }
+    }
+
+    // Copy of this functor shared by the clones of a composed pipeline
+    fn stage(&self) -> Arc<Mutex<Self>>
+    where
+        Functor: Clone,
+    {
+        Arc::new(Mutex::new(self.curry(Vec::new(), HashMap::new())))
+    }
+
+    // This functor around another function: bound inputs, key, signature and the cached
+    // results carry over
+    fn wrap<Wrapped>(&self, func: Wrapped) -> F<Wrapped, Args, Kwargs, R, Check>
+    where
+        Wrapped: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+    {
+        F {
+            func,
             args: self.args.clone(),
             kwargs: self.kwargs.clone(),
+            key_fn: self.key_fn.clone(),
             cache: self.cache.clone(),
+            disk: self.disk.clone(),
+            validation: self.validation.clone(),
+            errors: self.errors.clone(),
+            name: self.name.clone(),
+            logging: self.logging.clone(),
+            checking: PhantomData,
         }
     }
 
-    // Log call method
-    pub fn log_call(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, KwargsValue>, result: Result) {
-        println!("Calling {:?} with args {:?} and kwargs {:?} resulted in {:?}", self.func, args, kwargs, result);
+    fn snapshot_rows(&self) -> Vec<Row>
+    where
+        Args: ToJson,
+        Kwargs: ToJson,
+        R: ToJson,
+    {
+        self.cache_entries()
+            .map(|(args, kwargs, result)| Row {
+                args: Json::Array(args.iter().map(ToJson::to_json).collect()),
+                kwargs: Json::Object(kwargs.iter().map(|(key, value)| (key.clone(), value.to_json())).collect()),
+                result: result.to_json(),
+            })
+            .filter(|row| finite(&row.args) && finite(&row.kwargs) && finite(&row.result))
+            .collect()
     }
 
-    // Clear cache method
-    pub fn clear_cache(&mut self) {
-        self.cache.clear();
+    fn import_rows(&mut self, rows: Vec<Row>) -> Result<usize, SnapshotError>
+    where
+        Args: FromJson,
+        Kwargs: FromJson,
+        R: FromJson,
+    {
+        let mut imported = Vec::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let decode = || {
+                let args = Vec::<Args>::from_json(&row.args)?;
+                let kwargs = row
+                    .kwargs
+                    .as_object()?
+                    .iter()
+                    .map(|(key, value)| Some((key.clone(), Kwargs::from_json(value)?)))
+                    .collect::<Option<HashMap<KwargsKey, Kwargs>>>()?;
+                Some((CacheKey::new(args, kwargs, &self.key_fn), R::from_json(&row.result)?))
+            };
+            imported.push(decode().ok_or(SnapshotError::Entry { index })?);
+        }
+        let count = imported.len();
+        for (cache_key, result) in imported {
+            self.remember(cache_key, &result);
+        }
+        Ok(count)
+    }
+
+    // Cached result from memory, or from disk, which also puts it in memory
+    fn lookup(&mut self, cache_key: &CacheKey<Args, Kwargs>) -> Option<(R, CacheStatus)> {
+        if let Some(result) = self.cache.get(cache_key) {
+            return Some((result, CacheStatus::Hit));
+        }
+        let result = self.load(cache_key)?;
+        self.cache.insert(cache_key.clone(), result.clone());
+        Some((result, CacheStatus::DiskHit))
+    }
+
+    fn batch<Calls, Compute>(&mut self, inputs: Calls, compute: Compute) -> Result<Vec<R>, Check::Error>
+    where
+        Calls: IntoIterator<Item = (Vec<Args>, HashMap<KwargsKey, Kwargs>)>,
+        Compute: FnOnce(&Functor, Vec<Miss<Args, Kwargs>>) -> Vec<(usize, R, Duration)>,
+    {
+        // Distinct inputs, and which of them each input is
+        let mut positions = HashMap::new();
+        let mut keys = Vec::new();
+        let mut distinct = Vec::new();
+        let mut order = Vec::new();
+        for (args, kwargs) in inputs {
+            let (all_args, all_kwargs) = self.prepare(args, kwargs)?;
+            let cache_key = CacheKey::new(all_args.clone(), all_kwargs.clone(), &self.key_fn);
+            let next = keys.len();
+            let position = *positions.entry(cache_key.clone()).or_insert(next);
+            if position == next {
+                keys.push(cache_key);
+                distinct.push((all_args, all_kwargs));
+            }
+            order.push(position);
+        }
+
+        let mut results: Vec<Option<R>> = (0..keys.len()).map(|_| None).collect();
+        let mut misses = Vec::new();
+        for (position, (args, kwargs)) in distinct.into_iter().enumerate() {
+            let mut timer = Timeit::new();
+            let start = timer.__enter();
+            let found = self.lookup(&keys[position]);
+            timer.__exit(start);
+            match found {
+                Some((result, status)) => {
+                    self.log(&keys[position], status, &result, timer.elapsed());
+                    results[position] = Some(result);
+                }
+                None => misses.push((position, args, kwargs)),
+            }
+        }
+
+        for (position, result, elapsed) in compute(&self.func, misses) {
+            self.log(&keys[position], CacheStatus::Miss, &result, elapsed);
+            self.remember(keys[position].clone(), &result);
+            results[position] = Some(result);
+        }
+        Ok(order.into_iter().map(|position| results[position].clone().unwrap()).collect())
+    }
+
+    fn log(&self, cache_key: &CacheKey<Args, Kwargs>, status: CacheStatus, result: &R, elapsed: Duration) {
+        let logging = match &self.logging {
+            Some(logging) => logging,
+            None => return,
+        };
+        let kwargs: Vec<String> = cache_key.kwargs().iter().map(|(key, value)| format!("{}: {:?}", key, value)).collect();
+        let full = logging.level == LogLevel::Full;
+        logging.sink.write(&CallRecord {
+            functor: self.name.clone().unwrap_or_else(|| std::any::type_name::<Functor>().to_string()),
+            args: format!("{:?}", cache_key.args()),
+            kwargs: format!("{{{}}}", kwargs.join(", ")),
+            cache: status,
+            result: full.then(|| (logging.format_result)(result)),
+            duration: full.then_some(elapsed),
+        });
+    }
+
+    // Binds the call's inputs and checks them against the signature
+    fn prepare(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Result<Inputs<Args, Kwargs>, Check::Error> {
+        Check::check(self.bind(args, kwargs), |(all_args, all_kwargs)| self.validate(all_args, all_kwargs))
+    }
+
+    fn validate(&self, all_args: &[Args], all_kwargs: &mut HashMap<KwargsKey, Kwargs>) -> Result<(), SignatureError> {
+        // The functor's own signature comes first, its defaults are passed to the stages
+        for Validation { signature, validate, stage, .. } in &self.validation {
+            match stage {
+                None => validate(signature, all_args.len(), all_kwargs)?,
+                Some(StageInputs { args, kwargs }) => {
+                    // The stage fills in its own defaults
+                    let mut stage_kwargs = kwargs.clone();
+                    stage_kwargs.extend(all_kwargs.clone());
+                    let arg_count = match args {
+                        StageArgs::After(offset) => offset + all_args.len(),
+                        StageArgs::Fixed(count) => *count,
+                    };
+                    validate(signature, arg_count, &mut stage_kwargs)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Errors are only read back with ErrorCaching::Cache, as they are only written then. A
+    // wrapper like retry shares the disk cache of the functor it wraps, which may hold some
+    fn load(&self, cache_key: &CacheKey<Args, Kwargs>) -> Option<R> {
+        let persist = self.disk.as_ref()?;
+        let stored = persist.disk.get(&(persist.key)(cache_key.args(), cache_key.kwargs())?)?;
+        let result = (persist.decode)(&stored)?;
+        let skipped = self.errors.as_ref().filter(|errors| errors.caching != ErrorCaching::Cache);
+        match skipped {
+            Some(errors) if (errors.is_error)(&result) => None,
+            _ => Some(result),
+        }
+    }
+
+    // Caches a computed result, errors of a fallible functor as its ErrorCaching says
+    fn remember(&mut self, cache_key: CacheKey<Args, Kwargs>, result: &R) {
+        let failed = self.errors.as_ref().filter(|errors| (errors.is_error)(result));
+        match failed.map(|errors| errors.caching) {
+            None | Some(ErrorCaching::Cache) => {
+                self.store(&cache_key, result);
+                self.cache.insert(cache_key, result.clone());
+            }
+            Some(ErrorCaching::Skip) => {}
+            Some(ErrorCaching::Ttl(ttl)) => {
+                self.cache.insert_with_ttl(cache_key, result.clone(), ttl);
+            }
+        }
+    }
+
+    // A failed write only costs a recomputation in the next run
+    fn store(&self, cache_key: &CacheKey<Args, Kwargs>, result: &R) {
+        if let Some(persist) = &self.disk {
+            if let Some(key) = (persist.key)(cache_key.args(), cache_key.kwargs()) {
+                let _ = persist.disk.insert(&key, (persist.encode)(result));
+            }
+        }
+    }
+
+    fn bind(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> (Vec<Args>, HashMap<KwargsKey, Kwargs>) {
+        bind_args(&self.args, &self.kwargs, args, kwargs)
+    }
+}
+
+impl<Functor, Args, R, Check> F<Functor, Args, KwargsValue, R, Check>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> R,
+    Args: Clone + PartialEq + fmt::Debug,
+    R: Clone,
+    Check: Checking,
+{
+    // Declares the inputs the functor takes. The functor becomes Checked: `call` returns a
+    // Result and rejects anything else with a SignatureError instead of running the function
+    pub fn signature(mut self, signature: Signature) -> F<Functor, Args, KwargsValue, R, Checked> {
+        self.validation.retain(|validation| validation.stage.is_some());
+        self.validation.insert(0, Validation {
+            signature,
+            validate: |signature, arg_count, kwargs| signature.validate(arg_count, kwargs),
+            defaults: |signature, kwargs| signature.fill_defaults(kwargs),
+            stage: None,
+        });
+        self.into_checking()
+    }
+}
+
+// Wait before each retry of a failed call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    None,
+    Fixed(Duration),
+    // Doubles after every retry, capped at `max`
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    // Delay before the given retry, the first retry is 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        match *self {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+                initial.saturating_mul(factor).min(max)
+            }
+        }
     }
 }
 
-impl<Functor, Args, Kwargs> PartialEq for F<Functor, Args, Kwargs>
+// Error of a call cut short by `timeout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(pub Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "call timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+// Functors whose function can fail
+impl<Functor, Args, Kwargs, T, E, Check> F<Functor, Args, Kwargs, Result<T, E>, Check>
 where
-    Functor: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> Result,
-    Args: Clone + PartialEq + Hash + fmt::Debug,
-    Kwargs: Clone + PartialEq + Hash + fmt::Debug,
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> Result<T, E>,
+    Args: Clone + PartialEq + fmt::Debug,
+    Kwargs: Clone + PartialEq + fmt::Debug,
+    T: Clone,
+    E: Clone,
+    Check: Checking,
+{
+    // Selects how Err results are cached, by default they are kept like any other result.
+    // Errors are only written to a disk cache with ErrorCaching::Cache
+    pub fn error_caching(mut self, caching: ErrorCaching) -> Self {
+        self.errors = Some(ErrorPolicy { caching, is_error: Result::is_err });
+        self
+    }
+
+    // Call that reports a rejected signature through the function's own error type
+    pub fn try_call(&mut self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Result<T, E>
+    where
+        E: From<SignatureError>,
+    {
+        let (all_args, all_kwargs) = self.prepare(args, kwargs).map_err(Check::error_into)?;
+        self.run(all_args, all_kwargs)
+    }
+
+    // Like chain, but `next` only runs on an Ok result and an Err is passed straight
+    // through, like `?`. The composed functor caches errors as this one does
+    pub fn try_chain<Next, Output>(
+        &self,
+        next: Next,
+    ) -> TryF<impl TryStage<Args, Kwargs, Output, E>, Args, Kwargs, Output, E, Check>
+    where
+        Functor: Clone,
+        Next: Fn(Vec<T>, HashMap<KwargsKey, Kwargs>) -> Result<Output, E> + Clone,
+        Output: Clone,
+    {
+        let mut composed = self.chain(move |results: Vec<Result<T, E>>, kwargs: HashMap<KwargsKey, Kwargs>| {
+            results.into_iter().next().unwrap().and_then(|value| next(vec![value], kwargs))
+        });
+        composed.errors = self
+            .errors
+            .as_ref()
+            .map(|errors| ErrorPolicy { caching: errors.caching, is_error: Result::is_err });
+        composed
+    }
+
+    // Like map, but only Ok results are transformed
+    pub fn try_map<Transform, Output>(
+        &self,
+        transform_func: Transform,
+    ) -> TryF<impl TryStage<Args, Kwargs, Output, E>, Args, Kwargs, Output, E, Check>
+    where
+        Functor: Clone,
+        Transform: Fn(Vec<T>, HashMap<KwargsKey, Kwargs>) -> Output + Clone,
+        Output: Clone,
+    {
+        self.try_chain(move |values: Vec<T>, _: HashMap<KwargsKey, Kwargs>| Ok(transform_func(values, HashMap::new())))
+    }
+
+    // Retries a failed call up to `retries` more times, waiting as `backoff` says between
+    // attempts. The wrapped functor only caches successful results
+    pub fn retry(
+        &self,
+        retries: u32,
+        backoff: Backoff,
+    ) -> TryF<impl TryStage<Args, Kwargs, T, E>, Args, Kwargs, T, E, Check>
+    where
+        Functor: Clone,
+    {
+        let func = self.func.clone();
+        self.wrap_fallible(move |args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>| {
+            let mut result = func(args.clone(), kwargs.clone());
+            for retry in 1..=retries {
+                if result.is_ok() {
+                    break;
+                }
+                thread::sleep(backoff.delay(retry));
+                result = func(args.clone(), kwargs.clone());
+            }
+            result
+        })
+    }
+
+    // Gives up on a call after `duration` with Elapsed. Every call spawns a detached worker
+    // thread to run the function, and a call that times out leaves it running in the
+    // background until the function returns. A panic on the worker is resumed in the
+    // caller. The wrapped functor only caches successful results
+    pub fn timeout(&self, duration: Duration) -> TryF<impl TryStage<Args, Kwargs, T, E>, Args, Kwargs, T, E, Check>
+    where
+        Functor: Clone + Send + 'static,
+        Args: Send + 'static,
+        Kwargs: Send + 'static,
+        T: Send + 'static,
+        E: From<Elapsed> + Send + 'static,
+    {
+        let func = self.func.clone();
+        self.wrap_fallible(move |args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>| {
+            let (sender, receiver) = mpsc::channel();
+            let func = func.clone();
+            thread::spawn(move || {
+                let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(|| func(args, kwargs))));
+            });
+            match receiver.recv_timeout(duration) {
+                Ok(Ok(result)) => result,
+                Ok(Err(payload)) => panic::resume_unwind(payload),
+                Err(RecvTimeoutError::Timeout) => Err(Elapsed(duration).into()),
+                Err(RecvTimeoutError::Disconnected) => unreachable!("the worker sends before it exits"),
+            }
+        })
+    }
+
+    // Calls `other` with the same inputs when this functor's function fails. The wrapped
+    // functor only caches successful results
+    pub fn fallback<Other>(&self, other: Other) -> TryF<impl TryStage<Args, Kwargs, T, E>, Args, Kwargs, T, E, Check>
+    where
+        Functor: Clone,
+        Other: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> Result<T, E> + Clone,
+    {
+        let func = self.func.clone();
+        self.wrap_fallible(move |args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>| {
+            func(args.clone(), kwargs.clone()).or_else(|_| other(args, kwargs))
+        })
+    }
+
+    fn wrap_fallible<Wrapped>(&self, func: Wrapped) -> F<Wrapped, Args, Kwargs, Result<T, E>, Check>
+    where
+        Wrapped: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> Result<T, E>,
+    {
+        let mut wrapped = self.wrap(func);
+        wrapped.errors = Some(ErrorPolicy { caching: ErrorCaching::Skip, is_error: Result::is_err });
+        // Errors this functor cached would hide the wrapper's recovery
+        let failed: Vec<_> = wrapped.cache.iter().filter(|(_, result)| result.is_err()).map(|(key, _)| key.clone()).collect();
+        for cache_key in &failed {
+            wrapped.cache.remove(cache_key);
+        }
+        wrapped
+    }
+}
+
+// Type-erased cache of a functor shared with a Fabric
+pub(crate) trait FunctorCache: Send + Sync {
+    fn clear(&self);
+
+    fn stats(&self) -> CacheStats;
+}
+
+impl<Functor, Args, Kwargs, R, Check> FunctorCache for Mutex<F<Functor, Args, Kwargs, R, Check>>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Send,
+    Args: Clone + PartialEq + fmt::Debug + Send,
+    Kwargs: Clone + PartialEq + fmt::Debug + Send,
+    R: Clone + Send,
+    Check: Checking + Send,
+{
+    fn clear(&self) {
+        self.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear_cache();
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).cache_stats()
+    }
+}
+
+// Disk cache key, the positional args followed by the kwargs as an object. NaN and the
+// infinities are all written as null, so keys holding them would collide with each other
+// and with None; such calls are not persisted
+fn disk_key<Args: ToJson, Kwargs: ToJson>(args: &[Args], kwargs: &[(KwargsKey, Kwargs)]) -> Option<Json> {
+    let args = args.iter().map(ToJson::to_json).collect();
+    let kwargs = kwargs.iter().map(|(key, value)| (key.clone(), value.to_json())).collect();
+    let key = Json::Array(vec![Json::Array(args), Json::Object(kwargs)]);
+    finite(&key).then_some(key)
+}
+
+fn finite(json: &Json) -> bool {
+    match json {
+        Json::Number(n) => n.is_finite(),
+        Json::Array(items) => items.iter().all(finite),
+        Json::Object(fields) => fields.values().all(finite),
+        _ => true,
+    }
+}
+
+// Input of a batch call that was not cached: its position among the distinct inputs
+type Miss<Args, Kwargs> = (usize, Vec<Args>, HashMap<KwargsKey, Kwargs>);
+
+fn compute_all<Functor, Args, Kwargs, R>(func: &Functor, misses: Vec<Miss<Args, Kwargs>>) -> Vec<(usize, R, Duration)>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+{
+    misses
+        .into_iter()
+        .map(|(position, args, kwargs)| {
+            let mut timer = Timeit::new();
+            let start = timer.__enter();
+            let result = func(args, kwargs);
+            timer.__exit(start);
+            (position, result, timer.elapsed())
+        })
+        .collect()
+}
+
+// Calls one pipeline stage, returns its result and the kwargs it saw for the next stage
+fn run_stage<Functor, Args, Kwargs, R, Check>(
+    stage: &Mutex<F<Functor, Args, Kwargs, R, Check>>,
+    args: Vec<Args>,
+    kwargs: HashMap<KwargsKey, Kwargs>,
+) -> (R, HashMap<KwargsKey, Kwargs>)
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+    Args: Clone + PartialEq + fmt::Debug,
+    Kwargs: Clone + PartialEq + fmt::Debug,
+    R: Clone,
+    Check: Checking,
+{
+    // A stage that panicked left no partial cache entry behind
+    let mut stage = stage.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut forwarded = stage.kwargs.clone();
+    forwarded.extend(kwargs.clone());
+    (stage.call_stage(args, kwargs), forwarded)
+}
+
+// Bound args come first, call-time kwargs override bound ones
+pub(crate) fn bind_args<Args: Clone, Kwargs: Clone>(
+    bound_args: &[Args],
+    bound_kwargs: &HashMap<KwargsKey, Kwargs>,
+    args: Vec<Args>,
+    kwargs: HashMap<KwargsKey, Kwargs>,
+) -> (Vec<Args>, HashMap<KwargsKey, Kwargs>) {
+    let mut all_args = bound_args.to_vec();
+    all_args.extend(args);
+    let mut all_kwargs = bound_kwargs.clone();
+    all_kwargs.extend(kwargs);
+    (all_args, all_kwargs)
+}
+
+// Identity of a functor is its optional name and its bound args and kwargs, the function
+// itself can't be compared. Unnamed functors of one type with the same bound inputs are
+// equal even over different fn pointers or closure state, so name those to tell them apart
+impl<Functor, Args, Kwargs, R, Check> PartialEq for F<Functor, Args, Kwargs, R, Check>
+where
+    Args: PartialEq,
+    Kwargs: PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
-        self.func == other.func && self.args == other.args && self.kwargs == other.kwargs
+        self.name == other.name && self.args == other.args && self.kwargs == other.kwargs
     }
 }
 
-impl<Functor, Args, Kwargs> Eq for F<Functor, Args, Kwargs>
+impl<Functor, Args, Kwargs, R, Check> Eq for F<Functor, Args, Kwargs, R, Check>
 where
-    Functor: Fn(Vec<Args>, HashMap<KwargsKey, KwargsValue>) -> Result,
-    Args: Clone + PartialEq + Hash + fmt::Debug,
-    Kwargs: Clone + PartialEq + Hash + fmt::Debug,
+    Args: Eq,
+    Kwargs: Eq,
 {
-}
\ No newline at end of file
+}
+
+impl<Functor, Args, Kwargs, R, Check> Hash for F<Functor, Args, Kwargs, R, Check>
+where
+    Args: Hash,
+    Kwargs: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.args.hash(state);
+        // Kwargs in key order, like CacheKey
+        let mut kwargs: Vec<(&KwargsKey, &Kwargs)> = self.kwargs.iter().collect();
+        kwargs.sort_by(|a, b| a.0.cmp(b.0));
+        kwargs.hash(state);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::functor::KwargsKey;
+use super::json::{FromJson, Json, ToJson};
+
+// Keyword argument value. Floats compare and hash by their bits so every value can be
+// part of a cache key
+#[derive(Debug, Clone)]
+pub enum KwargsValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    List(Vec<KwargsValue>),
+}
+
+impl KwargsValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            KwargsValue::Int(_) => "int",
+            KwargsValue::Float(_) => "float",
+            KwargsValue::Bool(_) => "bool",
+            KwargsValue::Str(_) => "str",
+            KwargsValue::List(_) => "list",
+        }
+    }
+}
+
+impl PartialEq for KwargsValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (KwargsValue::Int(a), KwargsValue::Int(b)) => a == b,
+            (KwargsValue::Float(a), KwargsValue::Float(b)) => a.to_bits() == b.to_bits(),
+            (KwargsValue::Bool(a), KwargsValue::Bool(b)) => a == b,
+            (KwargsValue::Str(a), KwargsValue::Str(b)) => a == b,
+            (KwargsValue::List(a), KwargsValue::List(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for KwargsValue {}
+
+impl Hash for KwargsValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            KwargsValue::Int(n) => n.hash(state),
+            KwargsValue::Float(x) => x.to_bits().hash(state),
+            KwargsValue::Bool(b) => b.hash(state),
+            KwargsValue::Str(s) => s.hash(state),
+            KwargsValue::List(items) => items.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for KwargsValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KwargsValue::Int(n) => write!(f, "{}", n),
+            KwargsValue::Float(x) => write!(f, "{:?}", x),
+            KwargsValue::Bool(b) => write!(f, "{}", b),
+            KwargsValue::Str(s) => write!(f, "{:?}", s),
+            KwargsValue::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+// Numbers carry their variant as {"int": n} or {"float": x}, so Float(2.0) does not come
+// back as Int(2) and ints past 2^53, written as strings, do not come back as Str
+impl ToJson for KwargsValue {
+    fn to_json(&self) -> Json {
+        let (tag, value) = match self {
+            KwargsValue::Int(n) => ("int", n.to_json()),
+            KwargsValue::Float(x) => ("float", x.to_json()),
+            KwargsValue::Bool(b) => return b.to_json(),
+            KwargsValue::Str(s) => return s.to_json(),
+            KwargsValue::List(items) => return items.to_json(),
+        };
+        Json::Object(BTreeMap::from([(tag.to_string(), value)]))
+    }
+}
+
+impl FromJson for KwargsValue {
+    fn from_json(json: &Json) -> Option<Self> {
+        match json {
+            Json::Bool(b) => Some(KwargsValue::Bool(*b)),
+            Json::String(s) => Some(KwargsValue::Str(s.clone())),
+            Json::Array(items) => items.iter().map(KwargsValue::from_json).collect::<Option<_>>().map(KwargsValue::List),
+            Json::Object(object) if object.len() == 1 => match (object.get("int"), object.get("float")) {
+                (Some(n), None) => i64::from_json(n).map(KwargsValue::Int),
+                (None, Some(x)) => f64::from_json(x).map(KwargsValue::Float),
+                _ => None,
+            },
+            Json::Number(_) | Json::Null | Json::Object(_) => None,
+        }
+    }
+}
+
+impl From<i64> for KwargsValue {
+    fn from(n: i64) -> Self {
+        KwargsValue::Int(n)
+    }
+}
+
+impl From<i32> for KwargsValue {
+    fn from(n: i32) -> Self {
+        KwargsValue::Int(n as i64)
+    }
+}
+
+impl From<f64> for KwargsValue {
+    fn from(x: f64) -> Self {
+        KwargsValue::Float(x)
+    }
+}
+
+impl From<bool> for KwargsValue {
+    fn from(b: bool) -> Self {
+        KwargsValue::Bool(b)
+    }
+}
+
+impl From<&str> for KwargsValue {
+    fn from(s: &str) -> Self {
+        KwargsValue::Str(s.to_string())
+    }
+}
+
+impl From<String> for KwargsValue {
+    fn from(s: String) -> Self {
+        KwargsValue::Str(s)
+    }
+}
+
+impl<T: Into<KwargsValue>> From<Vec<T>> for KwargsValue {
+    fn from(items: Vec<T>) -> Self {
+        KwargsValue::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KwargsError {
+    Missing { key: String },
+    WrongType { key: String, expected: &'static str, found: KwargsValue },
+}
+
+impl fmt::Display for KwargsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KwargsError::Missing { key } => write!(f, "missing keyword argument '{}'", key),
+            KwargsError::WrongType { key, expected, found } => write!(
+                f,
+                "keyword argument '{}' must be {}, got {} {}",
+                key,
+                expected,
+                found.type_name(),
+                found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KwargsError {}
+
+// Rust types a keyword argument can be read as
+pub trait FromKwarg: Sized {
+    const EXPECTED: &'static str;
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self>;
+}
+
+impl FromKwarg for i64 {
+    const EXPECTED: &'static str = "an int";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        match value {
+            KwargsValue::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromKwarg for i32 {
+    const EXPECTED: &'static str = "an int in i32 range";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        i64::from_kwarg(value).and_then(|n| i32::try_from(n).ok())
+    }
+}
+
+impl FromKwarg for usize {
+    const EXPECTED: &'static str = "a non-negative int";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        i64::from_kwarg(value).and_then(|n| usize::try_from(n).ok())
+    }
+}
+
+// Ints are widened, so `scale=2` works where a float is expected
+impl FromKwarg for f64 {
+    const EXPECTED: &'static str = "a float";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        match value {
+            KwargsValue::Float(x) => Some(*x),
+            KwargsValue::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromKwarg for bool {
+    const EXPECTED: &'static str = "a bool";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        match value {
+            KwargsValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromKwarg for String {
+    const EXPECTED: &'static str = "a str";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        match value {
+            KwargsValue::Str(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromKwarg> FromKwarg for Vec<T> {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        match value {
+            KwargsValue::List(items) => items.iter().map(T::from_kwarg).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl FromKwarg for KwargsValue {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_kwarg(value: &KwargsValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+// Typed access to the kwargs a functor receives
+pub trait KwargsExt {
+    fn get_as<T: FromKwarg>(&self, key: &str) -> Result<T, KwargsError>;
+
+    // Ok(None) when the key is missing, a mistyped value is still an error
+    fn get_opt<T: FromKwarg>(&self, key: &str) -> Result<Option<T>, KwargsError>;
+
+    fn get_or<T: FromKwarg>(&self, key: &str, default: T) -> Result<T, KwargsError> {
+        Ok(self.get_opt(key)?.unwrap_or(default))
+    }
+}
+
+impl KwargsExt for HashMap<KwargsKey, KwargsValue> {
+    fn get_as<T: FromKwarg>(&self, key: &str) -> Result<T, KwargsError> {
+        self.get_opt(key)?.ok_or_else(|| KwargsError::Missing { key: key.to_string() })
+    }
+
+    fn get_opt<T: FromKwarg>(&self, key: &str) -> Result<Option<T>, KwargsError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => T::from_kwarg(value).map(Some).ok_or_else(|| KwargsError::WrongType {
+                key: key.to_string(),
+                expected: T::EXPECTED,
+                found: value.clone(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use super::fabric::{CallbackKind, Fabric};
+use super::json::{Json, JsonError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub kind: CallbackKind,
+    pub arg_type: Option<String>,
+    pub return_type: Option<String>,
+    pub description: Option<String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+// Serializable description of the callbacks a Fabric is expected to provide
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub callbacks: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    Json(JsonError),
+    // The JSON root has no "callbacks" array
+    MissingCallbacks,
+    Toml { line: usize, message: String },
+    Entry { index: usize, message: String },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Json(error) => write!(f, "{}", error),
+            ManifestError::MissingCallbacks => write!(f, "missing 'callbacks' array"),
+            ManifestError::Toml { line, message } => write!(f, "invalid TOML on line {}: {}", line, message),
+            ManifestError::Entry { index, message } => write!(f, "callback #{}: {}", index, message),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl From<JsonError> for ManifestError {
+    fn from(error: JsonError) -> Self {
+        ManifestError::Json(error)
+    }
+}
+
+// One way a live Fabric disagrees with a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    Missing { name: String },
+    Kind { name: String, expected: CallbackKind, found: CallbackKind },
+    ArgType { name: String, expected: String, found: Option<String> },
+    ReturnType { name: String, expected: String, found: Option<String> },
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestMismatch::Missing { name } => write!(f, "callback '{}' is not registered", name),
+            ManifestMismatch::Kind { name, expected, found } => write!(
+                f,
+                "callback '{}' is {} but the manifest expects {}",
+                name,
+                found.as_str(),
+                expected.as_str()
+            ),
+            ManifestMismatch::ArgType { name, expected, found } => write!(
+                f,
+                "callback '{}' takes {} but the manifest expects {}",
+                name,
+                found.as_deref().unwrap_or("no argument"),
+                expected
+            ),
+            ManifestMismatch::ReturnType { name, expected, found } => write!(
+                f,
+                "callback '{}' returns {} but the manifest expects {}",
+                name,
+                found.as_deref().unwrap_or("nothing"),
+                expected
+            ),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn from_fabric(fabric: &Fabric) -> Self {
+        let callbacks = fabric
+            .names()
+            .into_iter()
+            .map(|name| {
+                let info = fabric.info(name).unwrap();
+                ManifestEntry {
+                    name: name.to_string(),
+                    kind: info.kind,
+                    arg_type: info.arg_type.map(str::to_string),
+                    return_type: info.return_type.map(str::to_string),
+                    description: info.description.clone(),
+                    tags: info.tags.clone(),
+                }
+            })
+            .collect();
+        Manifest { callbacks }
+    }
+
+    // Every mismatch is reported, not just the first. Descriptions and tags are informational only
+    pub fn validate(&self, fabric: &Fabric) -> Result<(), Vec<ManifestMismatch>> {
+        let mut mismatches = Vec::new();
+        for entry in &self.callbacks {
+            let name = entry.name.clone();
+            let info = match fabric.info(&entry.name) {
+                Some(info) => info,
+                None => {
+                    mismatches.push(ManifestMismatch::Missing { name });
+                    continue;
+                }
+            };
+
+            if info.kind != entry.kind {
+                mismatches.push(ManifestMismatch::Kind { name, expected: entry.kind, found: info.kind });
+                continue;
+            }
+            if let Some(expected) = &entry.arg_type {
+                if info.arg_type != Some(expected.as_str()) {
+                    mismatches.push(ManifestMismatch::ArgType {
+                        name: name.clone(),
+                        expected: expected.clone(),
+                        found: info.arg_type.map(str::to_string),
+                    });
+                }
+            }
+            if let Some(expected) = &entry.return_type {
+                if info.return_type != Some(expected.as_str()) {
+                    mismatches.push(ManifestMismatch::ReturnType {
+                        name,
+                        expected: expected.clone(),
+                        found: info.return_type.map(str::to_string),
+                    });
+                }
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let callbacks = self
+            .callbacks
+            .iter()
+            .map(|entry| {
+                let mut object = BTreeMap::new();
+                object.insert("name".to_string(), Json::String(entry.name.clone()));
+                object.insert("kind".to_string(), Json::String(entry.kind.as_str().to_string()));
+                let optional = [
+                    ("arg_type", &entry.arg_type),
+                    ("return_type", &entry.return_type),
+                    ("description", &entry.description),
+                ];
+                for (key, value) in optional {
+                    if let Some(value) = value {
+                        object.insert(key.to_string(), Json::String(value.clone()));
+                    }
+                }
+                if !entry.tags.is_empty() {
+                    let tags = entry.tags.iter().map(|(k, v)| (k.clone(), Json::String(v.clone()))).collect();
+                    object.insert("tags".to_string(), Json::Object(tags));
+                }
+                Json::Object(object)
+            })
+            .collect();
+
+        let mut root = BTreeMap::new();
+        root.insert("callbacks".to_string(), Json::Array(callbacks));
+        Json::Object(root).to_string_pretty()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ManifestError> {
+        let root = Json::parse(text)?;
+        let entries = root
+            .get("callbacks")
+            .and_then(Json::as_array)
+            .ok_or(ManifestError::MissingCallbacks)?;
+
+        let mut callbacks = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            let object = entry
+                .as_object()
+                .ok_or(ManifestError::Entry { index, message: "expected an object".to_string() })?;
+            let mut fields = BTreeMap::new();
+            let mut tags = BTreeMap::new();
+            for (key, value) in object {
+                if key == "tags" {
+                    let object = value
+                        .as_object()
+                        .ok_or(ManifestError::Entry { index, message: "'tags' must be an object".to_string() })?;
+                    for (tag, value) in object {
+                        tags.insert(tag.clone(), string_field(index, tag, value)?);
+                    }
+                } else {
+                    fields.insert(key.clone(), string_field(index, key, value)?);
+                }
+            }
+            callbacks.push(entry_from_fields(index, fields, tags)?);
+        }
+        Ok(Manifest { callbacks })
+    }
+
+    // Array of [[callback]] tables, tags in a [callback.tags] sub-table
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        for (i, entry) in self.callbacks.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str("[[callback]]\n");
+            writeln!(out, "name = {}", Json::String(entry.name.clone())).unwrap();
+            writeln!(out, "kind = \"{}\"", entry.kind.as_str()).unwrap();
+            let optional = [
+                ("arg_type", &entry.arg_type),
+                ("return_type", &entry.return_type),
+                ("description", &entry.description),
+            ];
+            for (key, value) in optional {
+                if let Some(value) = value {
+                    writeln!(out, "{} = {}", key, Json::String(value.clone())).unwrap();
+                }
+            }
+            if !entry.tags.is_empty() {
+                out.push_str("\n[callback.tags]\n");
+                for (key, value) in &entry.tags {
+                    writeln!(out, "{} = {}", toml_key(key), Json::String(value.clone())).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    // Reads the subset of TOML written by `to_toml`
+    pub fn from_toml(text: &str) -> Result<Self, ManifestError> {
+        let mut tables: Vec<(BTreeMap<String, String>, BTreeMap<String, String>)> = Vec::new();
+        let mut in_tags = false;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| ManifestError::Toml { line: number + 1, message: message.to_string() };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "[[callback]]" {
+                tables.push((BTreeMap::new(), BTreeMap::new()));
+                in_tags = false;
+                continue;
+            }
+            if line == "[callback.tags]" {
+                if tables.is_empty() {
+                    return Err(error("[callback.tags] before any [[callback]]"));
+                }
+                in_tags = true;
+                continue;
+            }
+
+            let (key, value) = split_key(line).ok_or_else(|| error("expected 'key = value'"))?;
+            let key = key.ok_or_else(|| error("invalid key"))?;
+            let value = Json::parse(value.trim())
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .ok_or_else(|| error("expected a quoted string value"))?;
+
+            let (fields, tags) = tables.last_mut().ok_or_else(|| error("key outside of [[callback]]"))?;
+            if in_tags {
+                tags.insert(key, value);
+            } else {
+                fields.insert(key, value);
+            }
+        }
+
+        let callbacks = tables
+            .into_iter()
+            .enumerate()
+            .map(|(index, (fields, tags))| entry_from_fields(index, fields, tags))
+            .collect::<Result<_, _>>()?;
+        Ok(Manifest { callbacks })
+    }
+}
+
+impl Fabric {
+    pub fn manifest(&self) -> Manifest {
+        Manifest::from_fabric(self)
+    }
+}
+
+fn string_field(index: usize, key: &str, value: &Json) -> Result<String, ManifestError> {
+    value.as_str().map(str::to_string).ok_or(ManifestError::Entry {
+        index,
+        message: format!("'{}' must be a string", key),
+    })
+}
+
+fn entry_from_fields(
+    index: usize,
+    mut fields: BTreeMap<String, String>,
+    tags: BTreeMap<String, String>,
+) -> Result<ManifestEntry, ManifestError> {
+    let error = |message: String| ManifestError::Entry { index, message };
+    let name = fields.remove("name").ok_or_else(|| error("missing 'name'".to_string()))?;
+    let kind = fields.remove("kind").ok_or_else(|| error("missing 'kind'".to_string()))?;
+    let kind = CallbackKind::parse(&kind).ok_or_else(|| error(format!("unknown kind '{}'", kind)))?;
+
+    Ok(ManifestEntry {
+        name,
+        kind,
+        arg_type: fields.remove("arg_type"),
+        return_type: fields.remove("return_type"),
+        description: fields.remove("description"),
+        tags,
+    })
+}
+
+// Splits `key = value` after the key, so a quoted key may contain '='. The key is None
+// when its quotes don't hold a valid string
+fn split_key(line: &str) -> Option<(Option<String>, &str)> {
+    if !line.starts_with('"') {
+        let (key, value) = line.split_once('=')?;
+        return Some((Some(key.trim().to_string()), value));
+    }
+
+    let mut escaped = false;
+    let mut end = None;
+    for (i, c) in line.char_indices().skip(1) {
+        match c {
+            '"' if !escaped => {
+                end = Some(i);
+                break;
+            }
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    let end = end?;
+    let key = Json::parse(&line[..=end]).ok().and_then(|k| k.as_str().map(str::to_string));
+    let value = line[end + 1..].trim_start().strip_prefix('=')?;
+    Some((key, value))
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        Json::String(key.to_string()).to_string()
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::time::Duration;
+
+// Latency samples kept per callback for the percentile estimates
+const MAX_SAMPLES: usize = 1024;
+
+// Snapshot of one callback's invocation metrics
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CallbackStats {
+    pub calls: u64,
+    // Callbacks have no way to fail other than panicking
+    pub panics: u64,
+    // Calls whose result was not of the type the caller asked for. That is the caller's
+    // mistake, not the callback's, so it is kept apart from the panics
+    pub mismatches: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CallbackMetrics {
+    calls: u64,
+    panics: u64,
+    mismatches: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    samples: VecDeque<Duration>,
+}
+
+impl CallbackMetrics {
+    pub(crate) fn record(&mut self, elapsed: Duration, panicked: bool) {
+        if self.calls == 0 || elapsed < self.min {
+            self.min = elapsed;
+        }
+        if elapsed > self.max {
+            self.max = elapsed;
+        }
+        self.calls += 1;
+        self.total += elapsed;
+        if panicked {
+            self.panics += 1;
+        }
+
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(elapsed);
+    }
+
+    pub(crate) fn record_mismatch(&mut self) {
+        self.mismatches += 1;
+    }
+
+    pub(crate) fn stats(&self) -> CallbackStats {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort();
+
+        CallbackStats {
+            calls: self.calls,
+            panics: self.panics,
+            mismatches: self.mismatches,
+            total: self.total,
+            min: self.min,
+            max: self.max,
+            mean: if self.calls == 0 { Duration::ZERO } else { mean(self.total, self.calls) },
+            p50: percentile(&sorted, 0.50),
+            p95: percentile(&sorted, 0.95),
+            p99: percentile(&sorted, 0.99),
+        }
+    }
+}
+
+// Rounded down to the nanosecond
+fn mean(total: Duration, calls: u64) -> Duration {
+    let nanos = total.as_nanos() / calls as u128;
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+// Nearest-rank percentile over sorted samples
+fn percentile(sorted: &[Duration], quantile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Metric name, help text and the stats field it reports
+type Counter = (&'static str, &'static str, fn(&CallbackStats) -> u64);
+
+// Prometheus text exposition format, one series per callback
+pub(crate) fn prometheus(stats: &BTreeMap<String, CallbackStats>) -> String {
+    let mut out = String::new();
+
+    let counters: [Counter; 3] = [
+        ("fabric_callback_calls_total", "Number of callback invocations.", |s| s.calls),
+        ("fabric_callback_panics_total", "Number of callback invocations that panicked.", |s| s.panics),
+        (
+            "fabric_callback_type_mismatches_total",
+            "Number of callback invocations whose result had another type than requested.",
+            |s| s.mismatches,
+        ),
+    ];
+    for (metric, help, value) in counters {
+        writeln!(out, "# HELP {} {}", metric, help).unwrap();
+        writeln!(out, "# TYPE {} counter", metric).unwrap();
+        for (name, stats) in stats {
+            writeln!(out, "{}{{callback=\"{}\"}} {}", metric, escape(name), value(stats)).unwrap();
+        }
+    }
+
+    let metric = "fabric_callback_duration_seconds";
+    writeln!(out, "# HELP {} Callback latency in seconds.", metric).unwrap();
+    writeln!(out, "# TYPE {} summary", metric).unwrap();
+    for (name, stats) in stats {
+        let name = escape(name);
+        for (quantile, value) in [("0.5", stats.p50), ("0.95", stats.p95), ("0.99", stats.p99)] {
+            writeln!(
+                out,
+                "{}{{callback=\"{}\",quantile=\"{}\"}} {}",
+                metric,
+                name,
+                quantile,
+                value.as_secs_f64()
+            )
+            .unwrap();
+        }
+        writeln!(out, "{}_sum{{callback=\"{}\"}} {}", metric, name, stats.total.as_secs_f64()).unwrap();
+        writeln!(out, "{}_count{{callback=\"{}\"}} {}", metric, name, stats.calls).unwrap();
+    }
+    out
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
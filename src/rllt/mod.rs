@@ -1,6 +1,22 @@
+#![allow(unused_macros)]
+
 pub mod fabric;
 pub mod timeit;
 pub mod functor;
+pub mod clock;
+pub mod scheduler;
+pub mod state_machine;
+pub mod metrics;
+pub mod json;
+pub mod manifest;
+pub mod cache;
+pub mod sync_functor;
+pub mod disk_cache;
+pub mod kwargs;
+pub mod signature;
+pub mod async_functor;
+pub mod call_log;
+pub mod snapshot;
 
 macro_rules! ifndef {
     ($name:ident, $body:block) => {
@@ -149,9 +165,10 @@ macro_rules! unwrap_or_else_msg {
 macro_rules! vec {
     ($($x:expr),*) => {
         {
+            #[allow(unused_mut)]
             let mut temp_vec = Vec::new();
             $(
-                temp_vec.push($x);
+                Vec::push(&mut temp_vec, $x);
             )*
             temp_vec
         }
@@ -237,6 +254,18 @@ macro_rules! map {
         }
     };
 }
+// Typed kwargs for a functor call, e.g. kwargs!("factor" => 4, "label" => "x")
+macro_rules! kwargs {
+    ($($key:expr => $value:expr),*) => {
+        {
+            let mut temp_map = std::collections::HashMap::new();
+            $(
+                temp_map.insert($key.to_string(), $crate::rllt::kwargs::KwargsValue::from($value));
+            )*
+            temp_map
+        }
+    };
+}
 macro_rules! todo {
     ($msg:expr) => {
         compile_error!(concat!("TODO: ", $msg));
@@ -0,0 +1,363 @@
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::clock::{Clock, SystemClock};
+use super::fabric::Fabric;
+
+const SECS_PER_DAY: u64 = 86_400;
+// How far ahead a cron expression is searched before it is considered unsatisfiable
+const CRON_SEARCH_DAYS: u64 = 5 * 366;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CronError {
+    FieldCount(usize),
+    InvalidField(String),
+}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CronError::FieldCount(count) => write!(f, "expected 5 cron fields, found {}", count),
+            CronError::InvalidField(field) => write!(f, "invalid cron field '{}'", field),
+        }
+    }
+}
+
+impl std::error::Error for CronError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    // An Every job would be due again as soon as it fired
+    ZeroInterval,
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::ZeroInterval => write!(f, "interval must be longer than zero"),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+// Parsed "minute hour day-of-month month day-of-week" expression, evaluated in UTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Day fields starting with *, steps like */2 included, do not count as restricted
+    dom_any: bool,
+    dow_any: bool,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self, CronError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(CronError::FieldCount(fields.len()));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 mean Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(CronExpr {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            dom_any: fields[2].starts_with('*'),
+            dow_any: fields[4].starts_with('*'),
+        })
+    }
+
+    // First matching minute strictly after `after`
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let start = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut secs = start - start % 60 + 60;
+        let limit = secs + CRON_SEARCH_DAYS * SECS_PER_DAY;
+
+        while secs < limit {
+            let days = secs / SECS_PER_DAY;
+            let (_, month, day) = civil_from_days(days);
+            let weekday = (days + 4) % 7;
+            if !bit(self.months, month) || !self.day_matches(day, weekday) {
+                secs = (days + 1) * SECS_PER_DAY;
+                continue;
+            }
+
+            let hour = (secs % SECS_PER_DAY) / 3600;
+            if !bit(self.hours, hour) {
+                secs = secs - secs % 3600 + 3600;
+                continue;
+            }
+
+            let minute = (secs % 3600) / 60;
+            if !bit(self.minutes, minute) {
+                secs += 60;
+                continue;
+            }
+
+            return Some(UNIX_EPOCH + Duration::from_secs(secs));
+        }
+        None
+    }
+
+    // Standard cron semantics: when both day fields are restricted either may match,
+    // otherwise both must
+    fn day_matches(&self, day: u64, weekday: u64) -> bool {
+        let dom = bit(self.days_of_month, day);
+        let dow = bit(self.days_of_week, weekday);
+        if self.dom_any || self.dow_any {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+}
+
+fn bit(mask: u64, value: u64) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, CronError> {
+    let invalid = || CronError::InvalidField(field.to_string());
+    let number = |s: &str| -> Result<u64, CronError> {
+        let value = s.parse::<u64>().map_err(|_| invalid())?;
+        if value < min || value > max {
+            return Err(invalid());
+        }
+        Ok(value)
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else if let Some((low, high)) = range.split_once('-') {
+            (number(low)?, number(high)?)
+        } else {
+            let value = number(range)?;
+            // "5/15" means "every 15 starting at 5"
+            (value, if part.contains('/') { max } else { value })
+        };
+        if low > high {
+            return Err(invalid());
+        }
+
+        for value in (low..=high).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+// Days since 1970-01-01 to (year, month, day), proleptic Gregorian
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    After(Duration),
+    Cron(CronExpr),
+}
+
+#[derive(Debug)]
+struct Job {
+    name: String,
+    schedule: Schedule,
+    next: Option<SystemTime>,
+}
+
+// Owns a Fabric and fires its void callbacks by name on a schedule
+pub struct Scheduler {
+    fabric: Fabric,
+    clock: Arc<dyn Clock>,
+    jobs: Vec<Job>,
+    tick: Duration,
+    missed: usize,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("fabric", &self.fabric)
+            .field("jobs", &self.jobs)
+            .field("tick", &self.tick)
+            .field("missed", &self.missed)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    pub fn new(fabric: Fabric) -> Self {
+        Scheduler::with_clock(fabric, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(fabric: Fabric, clock: Arc<dyn Clock>) -> Self {
+        Scheduler {
+            fabric,
+            clock,
+            jobs: Vec::new(),
+            tick: Duration::from_millis(50),
+            missed: 0,
+        }
+    }
+
+    // Upper bound on how long the background thread sleeps between clock checks
+    pub fn set_tick(&mut self, tick: Duration) {
+        self.tick = tick;
+    }
+
+    pub fn every(&mut self, name: String, interval: Duration) -> Result<(), ScheduleError> {
+        if interval.is_zero() {
+            return Err(ScheduleError::ZeroInterval);
+        }
+        let next = self.clock.now() + interval;
+        self.push(name, Schedule::Every(interval), Some(next));
+        Ok(())
+    }
+
+    pub fn after(&mut self, name: String, delay: Duration) {
+        let next = self.clock.now() + delay;
+        self.push(name, Schedule::After(delay), Some(next));
+    }
+
+    pub fn cron(&mut self, name: String, expr: &str) -> Result<(), CronError> {
+        let cron = CronExpr::parse(expr)?;
+        let next = cron.next_after(self.clock.now());
+        self.push(name, Schedule::Cron(cron), next);
+        Ok(())
+    }
+
+    pub fn cancel(&mut self, name: &str) {
+        self.jobs.retain(|job| job.name != name);
+    }
+
+    pub fn fabric(&self) -> &Fabric {
+        &self.fabric
+    }
+
+    pub fn fabric_mut(&mut self) -> &mut Fabric {
+        &mut self.fabric
+    }
+
+    pub fn into_fabric(self) -> Fabric {
+        self.fabric
+    }
+
+    // Due jobs whose callback was not registered in the Fabric, they are not counted as fired
+    pub fn missed(&self) -> usize {
+        self.missed
+    }
+
+    // Fires every job that is due at the current clock time, returns how many fired
+    pub fn run_pending(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut fired = 0;
+
+        for job in &mut self.jobs {
+            let due = match job.next {
+                Some(next) => next <= now,
+                None => false,
+            };
+            if !due {
+                continue;
+            }
+
+            if self.fabric.execute_callback(&job.name) {
+                fired += 1;
+            } else {
+                self.missed += 1;
+            }
+
+            job.next = match &job.schedule {
+                Schedule::Every(interval) => {
+                    let next = job.next.unwrap() + *interval;
+                    // Missed intervals are skipped rather than replayed
+                    Some(if next <= now { now + *interval } else { next })
+                }
+                Schedule::After(_) => None,
+                Schedule::Cron(cron) => cron.next_after(now),
+            };
+        }
+
+        self.jobs.retain(|job| job.next.is_some());
+        fired
+    }
+
+    // Time until the earliest job is due, None when nothing is scheduled
+    pub fn time_until_next(&self) -> Option<Duration> {
+        let now = self.clock.now();
+        self.jobs
+            .iter()
+            .filter_map(|job| job.next)
+            .min()
+            .map(|next| next.duration_since(now).unwrap_or_default())
+    }
+
+    pub fn start(mut self) -> SchedulerHandle {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            loop {
+                self.run_pending();
+                let wait = self.time_until_next().map_or(self.tick, |wait| wait.min(self.tick));
+                match stopped.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+            self
+        });
+
+        SchedulerHandle { stop, thread }
+    }
+
+    fn push(&mut self, name: String, schedule: Schedule, next: Option<SystemTime>) {
+        self.jobs.push(Job { name, schedule, next });
+    }
+}
+
+// Running scheduler; dropping it without shutdown also stops the thread
+#[derive(Debug)]
+pub struct SchedulerHandle {
+    stop: Sender<()>,
+    thread: JoinHandle<Scheduler>,
+}
+
+impl SchedulerHandle {
+    // Stops the background thread after its current pass and hands the scheduler back
+    pub fn shutdown(self) -> Scheduler {
+        let _ = self.stop.send(());
+        self.thread.join().expect("Scheduler thread panicked")
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::functor::KwargsKey;
+use super::kwargs::KwargsValue;
+
+// Declared type of a keyword argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KwargType {
+    Int,
+    // Ints are accepted too, like KwargsExt::get_as::<f64>
+    Float,
+    Bool,
+    Str,
+    List,
+    Any,
+}
+
+impl KwargType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KwargType::Int => "int",
+            KwargType::Float => "float",
+            KwargType::Bool => "bool",
+            KwargType::Str => "str",
+            KwargType::List => "list",
+            KwargType::Any => "any",
+        }
+    }
+
+    pub fn accepts(&self, value: &KwargsValue) -> bool {
+        matches!(
+            (self, value),
+            (KwargType::Any, _)
+                | (KwargType::Int, KwargsValue::Int(_))
+                | (KwargType::Float, KwargsValue::Float(_) | KwargsValue::Int(_))
+                | (KwargType::Bool, KwargsValue::Bool(_))
+                | (KwargType::Str, KwargsValue::Str(_))
+                | (KwargType::List, KwargsValue::List(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct KwargSpec {
+    kind: KwargType,
+    // None for a required kwarg
+    default: Option<KwargsValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    Arity { min: usize, max: Option<usize>, found: usize },
+    MissingKwarg { name: String },
+    UnknownKwarg { name: String },
+    KwargType { name: String, expected: KwargType, found: &'static str },
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Arity { min, max, found } => match max {
+                Some(max) if max == min => write!(f, "expected {} positional args, got {}", min, found),
+                Some(max) => write!(f, "expected {} to {} positional args, got {}", min, max, found),
+                None => write!(f, "expected at least {} positional args, got {}", min, found),
+            },
+            SignatureError::MissingKwarg { name } => write!(f, "missing required keyword argument '{}'", name),
+            SignatureError::UnknownKwarg { name } => write!(f, "unexpected keyword argument '{}'", name),
+            SignatureError::KwargType { name, expected, found } => write!(
+                f,
+                "keyword argument '{}' must be {}, got {}",
+                name,
+                expected.as_str(),
+                found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+// Declared inputs of a functor: how many positional args it takes and which kwargs, with
+// their types and defaults. Unless `allow_unknown` is set, undeclared kwargs are rejected
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    min_args: usize,
+    max_args: Option<usize>,
+    kwargs: BTreeMap<KwargsKey, KwargSpec>,
+    allow_unknown: bool,
+}
+
+impl Default for Signature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Signature {
+    // Any number of positional args and no kwargs
+    pub fn new() -> Self {
+        Signature {
+            min_args: 0,
+            max_args: None,
+            kwargs: BTreeMap::new(),
+            allow_unknown: false,
+        }
+    }
+
+    pub fn args(self, count: usize) -> Self {
+        self.args_between(count, Some(count))
+    }
+
+    // `max` of None leaves the positional args unbounded
+    pub fn args_between(mut self, min: usize, max: Option<usize>) -> Self {
+        self.min_args = min;
+        self.max_args = max.map(|max| max.max(min));
+        self
+    }
+
+    pub fn required(mut self, name: &str, kind: KwargType) -> Self {
+        self.kwargs.insert(name.to_string(), KwargSpec { kind, default: None });
+        self
+    }
+
+    // The default is filled in when the kwarg is left out, it is not checked against `kind`
+    pub fn optional(mut self, name: &str, kind: KwargType, default: impl Into<KwargsValue>) -> Self {
+        self.kwargs.insert(name.to_string(), KwargSpec { kind, default: Some(default.into()) });
+        self
+    }
+
+    pub fn allow_unknown(mut self) -> Self {
+        self.allow_unknown = true;
+        self
+    }
+
+    // Fills in the defaults of missing optional kwargs without checking anything else
+    pub fn fill_defaults(&self, kwargs: &mut HashMap<KwargsKey, KwargsValue>) {
+        for (name, spec) in &self.kwargs {
+            if let Some(default) = &spec.default {
+                kwargs.entry(name.clone()).or_insert_with(|| default.clone());
+            }
+        }
+    }
+
+    // Checks a call's complete inputs and fills in the defaults of missing optional kwargs.
+    // The first problem found is reported: arity, then unknown, missing and mistyped kwargs
+    pub fn validate(&self, arg_count: usize, kwargs: &mut HashMap<KwargsKey, KwargsValue>) -> Result<(), SignatureError> {
+        if arg_count < self.min_args || self.max_args.is_some_and(|max| arg_count > max) {
+            return Err(SignatureError::Arity { min: self.min_args, max: self.max_args, found: arg_count });
+        }
+
+        if !self.allow_unknown {
+            let mut unknown: Vec<&KwargsKey> = kwargs.keys().filter(|name| !self.kwargs.contains_key(*name)).collect();
+            unknown.sort();
+            if let Some(name) = unknown.first() {
+                return Err(SignatureError::UnknownKwarg { name: name.to_string() });
+            }
+        }
+
+        for (name, spec) in &self.kwargs {
+            match kwargs.get(name) {
+                Some(value) if !spec.kind.accepts(value) => {
+                    return Err(SignatureError::KwargType {
+                        name: name.clone(),
+                        expected: spec.kind,
+                        found: value.type_name(),
+                    });
+                }
+                Some(_) => {}
+                None => match &spec.default {
+                    Some(default) => {
+                        kwargs.insert(name.clone(), default.clone());
+                    }
+                    None => return Err(SignatureError::MissingKwarg { name: name.clone() }),
+                },
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::json::{Json, JsonError};
+
+// Version of the snapshot layout, written into JSON snapshots
+const FORMAT: f64 = 1.0;
+
+const CSV_HEADER: [&str; 3] = ["args", "kwargs", "result"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Json(JsonError),
+    Csv { line: usize, message: String },
+    // The document is not a snapshot, or one of another format version
+    Format(String),
+    // The entry at `index` does not decode to the functor's args, kwargs and result types
+    Entry { index: usize },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Json(error) => write!(f, "{}", error),
+            SnapshotError::Csv { line, message } => write!(f, "invalid CSV snapshot at line {}: {}", line, message),
+            SnapshotError::Format(message) => write!(f, "invalid snapshot: {}", message),
+            SnapshotError::Entry { index } => write!(f, "snapshot entry {} does not match the functor's types", index),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<JsonError> for SnapshotError {
+    fn from(error: JsonError) -> Self {
+        SnapshotError::Json(error)
+    }
+}
+
+// One cached call, encoded
+pub(crate) struct Row {
+    pub(crate) args: Json,
+    pub(crate) kwargs: Json,
+    pub(crate) result: Json,
+}
+
+// {"format": 1, "entries": [{"args": [..], "kwargs": {..}, "result": ..}, ..]}
+pub(crate) fn write_json(rows: Vec<Row>) -> Json {
+    let entries = rows
+        .into_iter()
+        .map(|row| {
+            let mut entry = BTreeMap::new();
+            entry.insert("args".to_string(), row.args);
+            entry.insert("kwargs".to_string(), row.kwargs);
+            entry.insert("result".to_string(), row.result);
+            Json::Object(entry)
+        })
+        .collect();
+    let mut snapshot = BTreeMap::new();
+    snapshot.insert("format".to_string(), Json::Number(FORMAT));
+    snapshot.insert("entries".to_string(), Json::Array(entries));
+    Json::Object(snapshot)
+}
+
+pub(crate) fn read_json(snapshot: &Json) -> Result<Vec<Row>, SnapshotError> {
+    match snapshot.get("format").and_then(Json::as_f64) {
+        Some(format) if format == FORMAT => {}
+        Some(format) => return Err(SnapshotError::Format(format!("unsupported format {}", format))),
+        None => return Err(SnapshotError::Format("missing format".to_string())),
+    }
+    let entries = snapshot
+        .get("entries")
+        .and_then(Json::as_array)
+        .ok_or_else(|| SnapshotError::Format("missing entries".to_string()))?;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let field = |name: &str| entry.get(name).cloned().ok_or(SnapshotError::Entry { index });
+            Ok(Row { args: field("args")?, kwargs: field("kwargs")?, result: field("result")? })
+        })
+        .collect()
+}
+
+// A header line, then one line per entry whose cells hold compact JSON
+pub(crate) fn write_csv(rows: Vec<Row>) -> String {
+    let mut text = CSV_HEADER.join(",");
+    text.push('\n');
+    for row in rows {
+        let cells = [row.args, row.kwargs, row.result].map(|cell| quote(&cell.to_string()));
+        text.push_str(&cells.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+pub(crate) fn read_csv(text: &str) -> Result<Vec<Row>, SnapshotError> {
+    let mut records = parse_csv(text)?.into_iter();
+    match records.next() {
+        Some((_, header)) if header == CSV_HEADER => {}
+        _ => return Err(SnapshotError::Csv { line: 1, message: format!("expected header {}", CSV_HEADER.join(",")) }),
+    }
+    records
+        .map(|(line, cells)| {
+            let cells: [String; 3] = cells.try_into().map_err(|cells: Vec<String>| SnapshotError::Csv {
+                line,
+                message: format!("expected 3 fields, got {}", cells.len()),
+            })?;
+            let [args, kwargs, result] = cells.map(|cell| Json::parse(&cell));
+            Ok(Row { args: args?, kwargs: kwargs?, result: result? })
+        })
+        .collect()
+}
+
+fn quote(cell: &str) -> String {
+    format!("\"{}\"", cell.replace('"', "\"\""))
+}
+
+// RFC 4180 records with the line each starts on. Blank lines are skipped
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, SnapshotError> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut cells = Vec::new();
+        let mut cell = String::new();
+        loop {
+            match chars.next() {
+                Some('"') if cell.is_empty() => loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            cell.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            cell.push(c);
+                        }
+                        None => return Err(SnapshotError::Csv { line: start, message: "unterminated quote".to_string() }),
+                    }
+                },
+                Some(',') => cells.push(std::mem::take(&mut cell)),
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    break;
+                }
+                Some(c) => cell.push(c),
+            }
+        }
+        cells.push(cell);
+        if cells.len() > 1 || !cells[0].is_empty() {
+            records.push((start, cells));
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Write};
+
+use super::fabric::Fabric;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransitionError {
+    UnknownEvent(String),
+    NoTransition { state: String, event: String },
+    GuardRejected { state: String, event: String },
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransitionError::UnknownEvent(event) => write!(f, "unknown event '{}'", event),
+            TransitionError::NoTransition { state, event } => {
+                write!(f, "no transition for event '{}' from state '{}'", event, state)
+            }
+            TransitionError::GuardRejected { state, event } => {
+                write!(f, "guard rejected event '{}' in state '{}'", event, state)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+struct Transition {
+    event: String,
+    from: String,
+    to: String,
+    guard: Option<Box<dyn Fn() -> bool + Send + Sync + 'static>>,
+}
+
+// Named states and transitions whose hooks are void callbacks of an owned Fabric
+pub struct StateMachine {
+    fabric: Fabric,
+    states: Vec<String>,
+    initial: String,
+    current: String,
+    transitions: Vec<Transition>,
+    on_enter: HashMap<String, Vec<String>>,
+    on_exit: HashMap<String, Vec<String>>,
+    on_transition: HashMap<String, Vec<String>>,
+}
+
+impl Debug for StateMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateMachine")
+            .field("fabric", &self.fabric)
+            .field("states", &self.states)
+            .field("current", &self.current)
+            .field(
+                "transitions",
+                &self
+                    .transitions
+                    .iter()
+                    .map(|t| format!("{} --{}--> {}", t.from, t.event, t.to))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl StateMachine {
+    pub fn new(fabric: Fabric, initial: &str) -> Self {
+        StateMachine {
+            fabric,
+            states: vec![initial.to_string()],
+            initial: initial.to_string(),
+            current: initial.to_string(),
+            transitions: Vec::new(),
+            on_enter: HashMap::new(),
+            on_exit: HashMap::new(),
+            on_transition: HashMap::new(),
+        }
+    }
+
+    pub fn add_state(&mut self, name: &str) {
+        if !self.states.iter().any(|state| state == name) {
+            self.states.push(name.to_string());
+        }
+    }
+
+    pub fn add_transition(&mut self, event: &str, from: &str, to: &str) {
+        self.push_transition(event, from, to, None);
+    }
+
+    // The first transition for (state, event) whose guard passes is taken
+    pub fn add_guarded_transition<G>(&mut self, event: &str, from: &str, to: &str, guard: G)
+    where
+        G: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.push_transition(event, from, to, Some(Box::new(guard)));
+    }
+
+    pub fn on_enter(&mut self, state: &str, callback: &str) {
+        self.on_enter.entry(state.to_string()).or_default().push(callback.to_string());
+    }
+
+    pub fn on_exit(&mut self, state: &str, callback: &str) {
+        self.on_exit.entry(state.to_string()).or_default().push(callback.to_string());
+    }
+
+    pub fn on_transition(&mut self, event: &str, callback: &str) {
+        self.on_transition.entry(event.to_string()).or_default().push(callback.to_string());
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn can_trigger(&self, event: &str) -> bool {
+        self.find_transition(event).is_ok()
+    }
+
+    // Runs on_exit of the old state, on_transition of the event, then on_enter of the new state
+    pub fn trigger(&mut self, event: &str) -> Result<&str, TransitionError> {
+        let index = self.find_transition(event)?;
+        let transition = &self.transitions[index];
+
+        let hooks = [
+            self.on_exit.get(&transition.from),
+            self.on_transition.get(&transition.event),
+            self.on_enter.get(&transition.to),
+        ];
+        for callback in hooks.into_iter().flatten().flatten() {
+            self.fabric.execute_callback(callback);
+        }
+
+        self.current = transition.to.clone();
+        Ok(&self.current)
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial.clone();
+    }
+
+    pub fn fabric(&self) -> &Fabric {
+        &self.fabric
+    }
+
+    pub fn fabric_mut(&mut self) -> &mut Fabric {
+        &mut self.fabric
+    }
+
+    // Graphviz description; guarded transitions are dashed, the current state is bold
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph state_machine {\n    rankdir=LR;\n");
+        dot.push_str("    \"__start\" [shape=point];\n");
+        writeln!(dot, "    \"__start\" -> {};", quote(&self.initial)).unwrap();
+
+        for state in &self.states {
+            if *state == self.current {
+                writeln!(dot, "    {} [shape=circle, style=bold];", quote(state)).unwrap();
+            } else {
+                writeln!(dot, "    {} [shape=circle];", quote(state)).unwrap();
+            }
+        }
+
+        for transition in &self.transitions {
+            let style = if transition.guard.is_some() { ", style=dashed" } else { "" };
+            writeln!(
+                dot,
+                "    {} -> {} [label={}{}];",
+                quote(&transition.from),
+                quote(&transition.to),
+                quote(&transition.event),
+                style
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn push_transition(
+        &mut self,
+        event: &str,
+        from: &str,
+        to: &str,
+        guard: Option<Box<dyn Fn() -> bool + Send + Sync + 'static>>,
+    ) {
+        self.add_state(from);
+        self.add_state(to);
+        self.transitions.push(Transition {
+            event: event.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            guard,
+        });
+    }
+
+    fn find_transition(&self, event: &str) -> Result<usize, TransitionError> {
+        if !self.transitions.iter().any(|t| t.event == event) {
+            return Err(TransitionError::UnknownEvent(event.to_string()));
+        }
+
+        let mut rejected = false;
+        for (index, transition) in self.transitions.iter().enumerate() {
+            if transition.event != event || transition.from != self.current {
+                continue;
+            }
+            match &transition.guard {
+                Some(guard) if !guard() => rejected = true,
+                _ => return Ok(index),
+            }
+        }
+
+        let state = self.current.clone();
+        let event = event.to_string();
+        if rejected {
+            Err(TransitionError::GuardRejected { state, event })
+        } else {
+            Err(TransitionError::NoTransition { state, event })
+        }
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use super::cache::CacheStats;
+use super::functor::{bind_args, hash_key, CacheKey, FunctorCache, KeyFn, KwargsKey};
+
+const DEFAULT_SHARDS: usize = 16;
+
+// Outcome of one in-flight computation, shared with the callers waiting on it
+enum Flight<R> {
+    Running,
+    Done(R),
+    // The computing caller panicked, waiters retry
+    Abandoned,
+}
+
+struct InFlight<R> {
+    state: Mutex<Flight<R>>,
+    done: Condvar,
+}
+
+enum Slot<R> {
+    Ready(R),
+    Pending(Arc<InFlight<R>>),
+}
+
+type Shard<Args, Kwargs, R> = Mutex<HashMap<CacheKey<Args, Kwargs>, Slot<R>>>;
+
+// Memoizing functor callable through &self from many threads. The cache is split into
+// independently locked shards, and concurrent callers with the same key wait for a
+// single computation instead of all running it
+pub struct SyncF<Functor, Args, Kwargs, R> {
+    func: Functor,
+    args: Vec<Args>,
+    kwargs: HashMap<KwargsKey, Kwargs>,
+    key_fn: KeyFn<Args, Kwargs>,
+    shards: Vec<Shard<Args, Kwargs, R>>,
+    // Callers that waited on another caller's computation count as hits
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<Functor, Args, Kwargs, R> fmt::Debug for SyncF<Functor, Args, Kwargs, R>
+where
+    Args: fmt::Debug,
+    Kwargs: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncF")
+            .field("func", &std::any::type_name::<Functor>())
+            .field("args", &self.args)
+            .field("kwargs", &self.kwargs)
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+impl<Functor, Args, Kwargs, R> SyncF<Functor, Args, Kwargs, R>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R,
+    Args: Clone + PartialEq,
+    Kwargs: Clone + PartialEq,
+    R: Clone,
+{
+    pub fn new(func: Functor, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> Self
+    where
+        Args: Hash + 'static,
+        Kwargs: Hash + 'static,
+    {
+        SyncF::with_key_fn(func, args, kwargs, Arc::new(hash_key::<Args, Kwargs>))
+    }
+
+    pub fn with_key_fn(
+        func: Functor,
+        args: Vec<Args>,
+        kwargs: HashMap<KwargsKey, Kwargs>,
+        key_fn: KeyFn<Args, Kwargs>,
+    ) -> Self {
+        SyncF {
+            func,
+            args,
+            kwargs,
+            key_fn,
+            shards: (0..DEFAULT_SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // Number of independently locked cache shards, cached results are dropped
+    pub fn shards(mut self, count: usize) -> Self {
+        self.shards = (0..count.max(1)).map(|_| Mutex::new(HashMap::new())).collect();
+        self
+    }
+
+    pub fn call(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> R {
+        let (all_args, all_kwargs) = bind_args(&self.args, &self.kwargs, args, kwargs);
+        let cache_key = CacheKey::new(all_args.clone(), all_kwargs.clone(), &self.key_fn);
+        let shard = self.shard(&cache_key);
+
+        loop {
+            let mut slots = lock(shard);
+            let flight = match slots.get(&cache_key) {
+                Some(Slot::Ready(result)) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return result.clone();
+                }
+                Some(Slot::Pending(flight)) => flight.clone(),
+                None => {
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    let flight = Arc::new(InFlight { state: Mutex::new(Flight::Running), done: Condvar::new() });
+                    slots.insert(cache_key.clone(), Slot::Pending(flight.clone()));
+                    drop(slots);
+                    return self.compute(shard, cache_key, flight, all_args, all_kwargs);
+                }
+            };
+            drop(slots);
+
+            let mut state = lock(&flight.state);
+            while let Flight::Running = *state {
+                state = flight.done.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            if let Flight::Done(result) = &*state {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return result.clone();
+            }
+        }
+    }
+
+    pub fn cache_len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).values().filter(|slot| matches!(slot, Slot::Ready(_))).count())
+            .sum()
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: self.cache_len(),
+            ..CacheStats::default()
+        }
+    }
+
+    // Computations still running keep their result
+    pub fn clear_cache(&self) {
+        for shard in &self.shards {
+            lock(shard).retain(|_, slot| matches!(slot, Slot::Pending(_)));
+        }
+    }
+
+    pub fn invalidate(&self, args: Vec<Args>, kwargs: HashMap<KwargsKey, Kwargs>) -> bool {
+        let (all_args, all_kwargs) = bind_args(&self.args, &self.kwargs, args, kwargs);
+        let cache_key = CacheKey::new(all_args, all_kwargs, &self.key_fn);
+        let mut slots = lock(self.shard(&cache_key));
+        if let Some(Slot::Ready(_)) = slots.get(&cache_key) {
+            slots.remove(&cache_key);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn shard(&self, cache_key: &CacheKey<Args, Kwargs>) -> &Shard<Args, Kwargs, R> {
+        &self.shards[(cache_key.hash_value() % self.shards.len() as u64) as usize]
+    }
+
+    fn compute(
+        &self,
+        shard: &Shard<Args, Kwargs, R>,
+        cache_key: CacheKey<Args, Kwargs>,
+        flight: Arc<InFlight<R>>,
+        args: Vec<Args>,
+        kwargs: HashMap<KwargsKey, Kwargs>,
+    ) -> R {
+        let mut guard = Abandon { shard, cache_key: Some(cache_key), flight: &flight };
+        let result = (self.func)(args, kwargs);
+
+        let cache_key = guard.cache_key.take().unwrap();
+        lock(shard).insert(cache_key, Slot::Ready(result.clone()));
+        *lock(&flight.state) = Flight::Done(result.clone());
+        flight.done.notify_all();
+        result
+    }
+}
+
+impl<Functor, Args, Kwargs, R> FunctorCache for SyncF<Functor, Args, Kwargs, R>
+where
+    Functor: Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Send + Sync,
+    Args: Clone + PartialEq + Send + Sync,
+    Kwargs: Clone + PartialEq + Send + Sync,
+    R: Clone + Send,
+{
+    fn clear(&self) {
+        self.clear_cache();
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.cache_stats()
+    }
+}
+
+// Releases waiters and forgets the pending slot if the computation unwinds
+struct Abandon<'a, Args: PartialEq, Kwargs: PartialEq, R> {
+    shard: &'a Shard<Args, Kwargs, R>,
+    cache_key: Option<CacheKey<Args, Kwargs>>,
+    flight: &'a InFlight<R>,
+}
+
+impl<Args: PartialEq, Kwargs: PartialEq, R> Drop for Abandon<'_, Args, Kwargs, R> {
+    fn drop(&mut self) {
+        if let Some(cache_key) = self.cache_key.take() {
+            lock(self.shard).remove(&cache_key);
+            *lock(&self.flight.state) = Flight::Abandoned;
+            self.flight.done.notify_all();
+        }
+    }
+}
+
+// A panicking computation never holds a shard lock, so poisoning carries no broken state
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -1,29 +1,39 @@
 use std::time::{Instant, Duration};
-pub(crate) struct Timeit {
+pub struct Timeit {
     t: Duration,
 }
 
+impl Default for Timeit {
+    fn default() -> Self {
+        Timeit::new()
+    }
+}
+
 impl Timeit {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Timeit {
             t: Duration::default(),
         }
     }
 
-    pub(crate) fn __enter(&mut self) -> Instant {
+    pub fn __enter(&mut self) -> Instant {
         Instant::now()
     }
 
-    pub(crate) fn __exit(&mut self, start: Instant) {
+    pub fn __exit(&mut self, start: Instant) {
         let elapsed = start.elapsed();
         self.t += elapsed;
     }
 
-    pub(crate) fn __str(&self) -> String {
+    pub fn elapsed(&self) -> Duration {
+        self.t
+    }
+
+    pub fn __str(&self) -> String {
         format!("Elapsed time is {:.6} seconds", self.t.as_secs_f64())
     }
 
-    pub(crate) fn timeit<F, R>(func: F) -> R
+    pub fn timeit<F, R>(func: F) -> R
     where
         F: FnOnce() -> R,
     {
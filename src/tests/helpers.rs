@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::rllt::functor::KwargsKey;
+
+// Real invocations of the functions wrapped by `counted`, clones share one count
+#[derive(Debug, Clone, Default)]
+pub struct Calls(Arc<AtomicUsize>);
+
+impl Calls {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// Wraps `func` so every real invocation bumps `calls`. The wrapper is Send and Sync
+// whenever `func` is, so it also works for SyncF and Fabric callbacks
+pub fn counted<Args, Kwargs, R>(
+    calls: &Calls,
+    func: impl Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Clone,
+) -> impl Fn(Vec<Args>, HashMap<KwargsKey, Kwargs>) -> R + Clone {
+    let calls = calls.clone();
+    move |args, kwargs| {
+        calls.0.fetch_add(1, Ordering::SeqCst);
+        func(args, kwargs)
+    }
+}
+
+// Fresh location under the temp dir, unique per test and process
+pub fn scratch(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("librllt-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    let _ = fs::remove_file(&path);
+    path
+}
@@ -1,4 +1,16 @@
+#[cfg(test)]
+mod helpers;
 mod test_defines;
 mod test_fabric;
 mod test_timeit;
-mod test_functor;
\ No newline at end of file
+mod test_functor;
+mod test_scheduler;
+mod test_state_machine;
+mod test_json;
+mod test_manifest;
+mod test_cache;
+mod test_sync_functor;
+mod test_disk_cache;
+mod test_async_functor;
+mod test_call_log;
+mod test_snapshot;
\ No newline at end of file
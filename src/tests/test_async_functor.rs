@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::async_functor::{block_on, AsyncF};
+    use crate::rllt::functor::{KwargsKey, KwargsValue};
+    use std::collections::HashMap;
+    use std::future::{poll_fn, Future};
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
+    use std::thread;
+    use std::time::Duration;
+
+    // Pending on the first poll, so concurrent calls overlap
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    // Drives both futures on the current thread until they are done
+    fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+        let (mut a, mut b) = (pin!(a), pin!(b));
+        let (mut a_out, mut b_out) = (None, None);
+        block_on(poll_fn(|cx| {
+            if a_out.is_none() {
+                if let Poll::Ready(out) = a.as_mut().poll(cx) {
+                    a_out = Some(out);
+                }
+            }
+            if b_out.is_none() {
+                if let Poll::Ready(out) = b.as_mut().poll(cx) {
+                    b_out = Some(out);
+                }
+            }
+            if a_out.is_some() && b_out.is_some() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+        (a_out.unwrap(), b_out.unwrap())
+    }
+
+    #[test]
+    fn test_memoizes_awaited_result() {
+        let calls = AtomicUsize::new(0);
+        let f = AsyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    YieldOnce(false).await;
+                    args.iter().sum::<i32>()
+                }
+            },
+            vec![1],
+            HashMap::new(),
+        );
+
+        assert_eq!(block_on(f.call(vec![2], HashMap::new())), 3);
+        assert_eq!(block_on(f.call(vec![2], HashMap::new())), 3);
+        assert_eq!(block_on(f.call(vec![3], HashMap::new())), 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(f.cache_len(), 2);
+
+        assert!(f.invalidate(vec![2], HashMap::new()));
+        assert!(!f.invalidate(vec![2], HashMap::new()));
+        f.clear_cache();
+        assert_eq!(f.cache_len(), 0);
+    }
+
+    #[test]
+    fn test_coalesces_in_flight_calls() {
+        let calls = AtomicUsize::new(0);
+        let f = AsyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    YieldOnce(false).await;
+                    args[0] * 10
+                }
+            },
+            vec![],
+            HashMap::new(),
+        );
+
+        assert_eq!(join(f.call(vec![4], HashMap::new()), f.call(vec![4], HashMap::new())), (40, 40));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(join(f.call(vec![5], HashMap::new()), f.call(vec![6], HashMap::new())), (50, 60));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_dropped_leader_hands_over() {
+        let calls = AtomicUsize::new(0);
+        let f = AsyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    YieldOnce(false).await;
+                    args[0]
+                }
+            },
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut waiter = pin!(f.call(vec![7], HashMap::new()));
+        {
+            let mut leader = pin!(f.call(vec![7], HashMap::new()));
+            block_on(poll_fn(|cx| {
+                assert!(leader.as_mut().poll(cx).is_pending());
+                assert!(waiter.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            }));
+        }
+
+        // The waiter takes over the computation
+        assert_eq!(block_on(waiter), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_block_on_waits_for_other_threads() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let future = poll_fn(move |cx| match receiver.try_recv() {
+            Ok(value) => Poll::Ready(value),
+            Err(_) => {
+                let waker = cx.waker().clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(5));
+                    waker.wake();
+                });
+                Poll::Pending
+            }
+        });
+
+        thread::spawn(move || sender.send(42).unwrap());
+        assert_eq!(block_on(future), 42);
+    }
+}
@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::cache::{Cache, CachePolicy};
+    use crate::rllt::clock::MockClock;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn filled(policy: CachePolicy) -> Cache<&'static str, i32> {
+        let mut cache = Cache::new(policy);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        cache
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = filled(CachePolicy::Lru(3));
+        cache.get(&"a");
+
+        assert_eq!(cache.insert("d", 4), 1);
+        assert!(!cache.contains_key(&"b"));
+        assert!(cache.contains_key(&"a"));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_lfu_evicts_least_frequently_used() {
+        let mut cache = filled(CachePolicy::Lfu(3));
+        cache.get(&"a");
+        cache.get(&"a");
+        cache.get(&"b");
+        cache.get(&"c");
+        cache.get(&"c");
+
+        cache.insert("d", 4);
+        assert!(!cache.contains_key(&"b"));
+
+        // The new entry has no hits yet, so it goes next
+        cache.insert("e", 5);
+        assert!(!cache.contains_key(&"d"));
+        assert!(cache.contains_key(&"a") && cache.contains_key(&"c"));
+    }
+
+    #[test]
+    fn test_fifo_ignores_access() {
+        let mut cache = filled(CachePolicy::Fifo(3));
+        cache.get(&"a");
+        cache.get(&"a");
+
+        cache.insert("d", 4);
+        assert!(!cache.contains_key(&"a"));
+        assert_eq!(cache.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn test_unbounded_and_zero_capacity() {
+        let mut unbounded = filled(CachePolicy::Unbounded);
+        assert_eq!(unbounded.capacity(), None);
+        assert_eq!(unbounded.insert("d", 4), 0);
+        assert_eq!(unbounded.len(), 4);
+
+        let mut disabled = filled(CachePolicy::Lru(0));
+        assert!(disabled.is_empty());
+        assert_eq!(disabled.get(&"a"), None);
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut cache = filled(CachePolicy::Fifo(3));
+        assert_eq!(cache.insert("a", 10), 0);
+        assert_eq!(cache.get(&"a"), Some(10));
+
+        // "a" was re-inserted last, so "b" is now the oldest
+        cache.insert("d", 4);
+        assert!(!cache.contains_key(&"b"));
+
+        assert_eq!(cache.remove(&"a"), Some(10));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_ttl_survives_policy_change() {
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut cache: Cache<&str, i32> = Cache::new(CachePolicy::Unbounded);
+        cache.set_clock(clock.clone());
+        cache.set_ttl(Some(Duration::from_secs(1)), false);
+        cache.set_policy(CachePolicy::Fifo(4));
+
+        cache.insert("a", 1);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"a"), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.ttl(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_insert_with_ttl() {
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut cache: Cache<&str, i32> = Cache::new(CachePolicy::Unbounded);
+        cache.set_clock(clock.clone());
+
+        cache.insert("kept", 1);
+        cache.insert_with_ttl("short", 2, Duration::from_secs(2));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get(&"short"), None);
+        assert_eq!(cache.get(&"kept"), Some(1));
+
+        // The shorter of the entry's and the cache's TTL applies
+        cache.set_ttl(Some(Duration::from_secs(1)), false);
+        cache.insert_with_ttl("long", 3, Duration::from_secs(10));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"long"), None);
+    }
+
+    #[test]
+    fn test_expired_entries_do_not_count() {
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut cache = filled(CachePolicy::Lru(3));
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl("b", 2, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(cache.len(), cache.iter().count());
+        assert_eq!((cache.len(), cache.stats().size), (2, 2));
+        assert!(!cache.contains_key(&"b"));
+
+        // The expired entry makes room, so no live entry is evicted
+        assert_eq!(cache.insert("d", 4), 0);
+        assert_eq!(cache.stats().expirations, 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_deadlines_follow_ttl_changes_and_refreshes() {
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut cache = Cache::new(CachePolicy::Unbounded);
+        cache.set_clock(clock.clone());
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        cache.set_ttl(Some(Duration::from_secs(2)), true);
+
+        // Entries stored before the TTL was set expire under it too
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"a"), Some(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.purge_expired(), 2);
+
+        // The refresh moved "a" back, lifting the TTL keeps it for good
+        cache.set_ttl(None, false);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.get(&"a"), Some(1));
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::call_log::{CacheStatus, CallRecord, FileSink, LogSink, MemorySink};
+    use crate::tests::helpers::scratch;
+    use std::fs;
+    use std::time::Duration;
+
+    fn record(result: Option<&str>, duration: Option<Duration>) -> CallRecord {
+        CallRecord {
+            functor: "square".to_string(),
+            args: "[3]".to_string(),
+            kwargs: "{}".to_string(),
+            cache: CacheStatus::Miss,
+            result: result.map(str::to_string),
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_record_display() {
+        assert_eq!(record(None, None).to_string(), "square(args=[3], kwargs={}) cache=miss");
+        assert_eq!(
+            record(Some("9"), Some(Duration::from_millis(2))).to_string(),
+            "square(args=[3], kwargs={}) cache=miss -> 9 in 2ms"
+        );
+    }
+
+    #[test]
+    fn test_memory_sink() {
+        let sink = MemorySink::new();
+        sink.write(&record(None, None));
+        sink.write(&record(Some("9"), None));
+
+        assert_eq!(sink.records().len(), 2);
+        assert_eq!(sink.records()[1].result.as_deref(), Some("9"));
+        sink.clear();
+        assert!(sink.records().is_empty());
+    }
+
+    #[test]
+    fn test_file_sink_appends_lines() {
+        let path = scratch("call-log.log");
+
+        FileSink::append(&path).unwrap().write(&record(None, None));
+        FileSink::append(&path).unwrap().write(&record(Some("9"), None));
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), vec![
+            "square(args=[3], kwargs={}) cache=miss",
+            "square(args=[3], kwargs={}) cache=miss -> 9"
+        ]);
+        fs::remove_file(&path).unwrap();
+    }
+}
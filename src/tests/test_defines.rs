@@ -1,5 +1,5 @@
-use std::any::Any;
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
 
     define!(TEST_U32_CONST: u32, 42);
@@ -22,6 +22,7 @@ mod tests {
         assert_eq!(TEST_STR_CONST, "Hello, World!");
     }
 
+    #[test]
     fn test_function() {
         let result = test_func();
         assert_eq!(*result.downcast_ref::<i32>().unwrap(), 30);
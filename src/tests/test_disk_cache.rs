@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::rllt::disk_cache::{fnv1a, DiskCache};
+    use crate::rllt::json::Json;
+    use crate::tests::helpers::scratch;
+
+    fn key(n: i32) -> Json {
+        Json::Array(vec![Json::Number(n as f64), Json::String("k".to_string())])
+    }
+
+    #[test]
+    fn test_directory_survives_reopening() {
+        let path = scratch("dir-reopen");
+        let cache = DiskCache::directory(&path, "v1").unwrap();
+        assert_eq!(cache.get(&key(1)), None);
+        cache.insert(&key(1), Json::Number(10.0)).unwrap();
+        cache.insert(&key(2), Json::String("twenty".to_string())).unwrap();
+        drop(cache);
+
+        let cache = DiskCache::directory(&path, "v1").unwrap();
+        assert_eq!(cache.get(&key(1)), Some(Json::Number(10.0)));
+        assert_eq!(cache.get(&key(2)), Some(Json::String("twenty".to_string())));
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.remove(&key(1)).unwrap());
+        assert!(!cache.remove(&key(1)).unwrap());
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writes_of_one_entry() {
+        let path = scratch("dir-concurrent");
+        let cache = DiskCache::directory(&path, "v1").unwrap();
+        std::thread::scope(|scope| {
+            for n in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        cache.insert(&key(1), Json::Number(n as f64)).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert!(cache.get(&key(1)).is_some_and(|value| value.as_f64().is_some_and(|n| n < 8.0)));
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 1);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_survives_reopening() {
+        let path = scratch("file-reopen.jsonl");
+        let cache = DiskCache::file(&path, "v1").unwrap();
+        cache.insert(&key(1), Json::Number(10.0)).unwrap();
+        cache.insert(&key(1), Json::Number(11.0)).unwrap();
+        cache.insert(&key(2), Json::Bool(true)).unwrap();
+        drop(cache);
+
+        let cache = DiskCache::file(&path, "v1").unwrap();
+        assert_eq!(cache.get(&key(1)), Some(Json::Number(11.0)));
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.remove(&key(2)).unwrap());
+        let cache = DiskCache::file(&path, "v1").unwrap();
+        assert_eq!(cache.get(&key(2)), None);
+        assert_eq!(cache.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_version_change_invalidates() {
+        let dir = scratch("dir-version");
+        DiskCache::directory(&dir, "v1").unwrap().insert(&key(1), Json::Null).unwrap();
+        let cache = DiskCache::directory(&dir, "v2").unwrap();
+        assert_eq!(cache.get(&key(1)), None);
+        assert_eq!(cache.len(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let file = scratch("file-version.jsonl");
+        DiskCache::file(&file, "v1").unwrap().insert(&key(1), Json::Null).unwrap();
+        let cache = DiskCache::file(&file, "v2").unwrap();
+        assert_eq!(cache.get(&key(1)), None);
+        // Lines of other versions stay in the file, also when it is rewritten
+        cache.insert(&key(2), Json::Null).unwrap();
+        assert!(cache.remove(&key(2)).unwrap());
+        let text = fs::read_to_string(&file).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        assert!(text.contains("\"v1\""));
+        assert_eq!(DiskCache::file(&file, "v1").unwrap().get(&key(1)), Some(Json::Null));
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_clear_leaves_other_files_alone() {
+        let dir = scratch("dir-shared");
+        let cache = DiskCache::directory(&dir, "v1").unwrap();
+        cache.insert(&key(1), Json::Null).unwrap();
+        fs::write(dir.join("settings.json"), "{}").unwrap();
+        fs::write(dir.join("0123456789ABCDEF.json"), "{}").unwrap();
+
+        assert_eq!(cache.len(), 1);
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+        let mut left: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, vec!["0123456789ABCDEF.json", "settings.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,13 @@
 #[cfg(test)]
 mod tests {
-    use crate::rllt::fabric::Fabric;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::Arc;
+    use crate::rllt::fabric::{Fabric, FabricError};
+    use crate::rllt::cache::CachePolicy;
+    use crate::rllt::functor::{KwargsKey, KwargsValue, F};
+    use crate::rllt::sync_functor::SyncF;
+    use crate::tests::helpers::{counted, Calls};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_add_and_execute_callback() {
@@ -44,7 +49,7 @@ mod tests {
             counter_clone2.store(true, Ordering::SeqCst);
         });
 
-        fabric.execute();
+        fabric.execute().unwrap();
         assert!(counter.load(Ordering::SeqCst));
     }
 
@@ -96,4 +101,254 @@ mod tests {
         assert_eq!(result1, Some(11));
         assert_eq!(result2, Some(5));
     }
+
+    fn recording_callback(log: &Arc<Mutex<Vec<String>>>, name: &str) -> impl Fn() + Send + Sync + 'static {
+        let log = log.clone();
+        let name = name.to_string();
+        move || log.lock().unwrap().push(name.clone())
+    }
+
+    #[test]
+    fn test_execute_in_dependency_order() {
+        let mut fabric = Fabric::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        fabric.add_callback_after("app.start".to_string(), &["db.migrate", "cache.init"], recording_callback(&log, "app.start"));
+        fabric.add_callback_after("db.migrate".to_string(), &["db.init"], recording_callback(&log, "db.migrate"));
+        fabric.add_callback("db.init".to_string(), recording_callback(&log, "db.init"));
+        fabric.add_callback("cache.init".to_string(), recording_callback(&log, "cache.init"));
+
+        assert_eq!(fabric.execution_order().unwrap(), ["cache.init", "db.init", "db.migrate", "app.start"]);
+        fabric.execute().unwrap();
+        assert_eq!(*log.lock().unwrap(), ["cache.init", "db.init", "db.migrate", "app.start"]);
+    }
+
+    #[test]
+    fn test_dependency_cycle_is_reported() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback_after("a".to_string(), &["c"], || {});
+        fabric.add_callback_after("b".to_string(), &["a"], || {});
+        fabric.add_callback_after("c".to_string(), &["b"], || {});
+        fabric.add_callback_after("d".to_string(), &["a"], || {});
+
+        let error = fabric.execute().unwrap_err();
+        assert_eq!(error, FabricError::Cycle(vec!["a".to_string(), "c".to_string(), "b".to_string(), "a".to_string()]));
+        assert_eq!(error.to_string(), "dependency cycle: a -> c -> b -> a");
+    }
+
+    #[test]
+    fn test_missing_dependency() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback_after("app.start".to_string(), &["db.init"], || {});
+
+        assert_eq!(
+            fabric.execute(),
+            Err(FabricError::MissingDependency {
+                callback: "app.start".to_string(),
+                dependency: "db.init".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute_parallel_respects_dependencies() {
+        let mut fabric = Fabric::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        fabric.add_callback("db.init".to_string(), recording_callback(&log, "db.init"));
+        fabric.add_callback("cache.init".to_string(), recording_callback(&log, "cache.init"));
+        fabric.add_callback_after("db.migrate".to_string(), &["db.init"], recording_callback(&log, "db.migrate"));
+        fabric.add_callback_after("app.start".to_string(), &["db.migrate", "cache.init"], recording_callback(&log, "app.start"));
+
+        fabric.execute_parallel().unwrap();
+
+        let log = log.lock().unwrap();
+        let position = |name: &str| log.iter().position(|entry| entry == name).unwrap();
+        assert_eq!(log.len(), 4);
+        assert!(position("db.init") < position("db.migrate"));
+        assert!(position("db.migrate") < position("app.start"));
+        assert!(position("cache.init") < position("app.start"));
+    }
+
+    #[test]
+    fn test_execute_parallel_reports_cycle() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback_after("a".to_string(), &["a"], || {});
+
+        assert_eq!(fabric.execute_parallel(), Err(FabricError::Cycle(vec!["a".to_string(), "a".to_string()])));
+    }
+
+    #[test]
+    fn test_callback_stats() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback("sleepy".to_string(), || std::thread::sleep(std::time::Duration::from_millis(2)));
+        fabric.add_callback_with_args("inc".to_string(), |x: &i32| -> i32 { x + 1 });
+
+        for _ in 0..3 {
+            fabric.execute_callback("sleepy");
+        }
+        let _: Option<i32> = fabric.execute_callback_with_args("inc", 1);
+        let _: Option<String> = fabric.execute_callback_with_args("inc", 1);
+
+        let sleepy = fabric.stats("sleepy").unwrap();
+        assert_eq!(sleepy.calls, 3);
+        assert_eq!(sleepy.mismatches, 0);
+        assert!(sleepy.min >= std::time::Duration::from_millis(2));
+        assert!(sleepy.min <= sleepy.p50 && sleepy.p50 <= sleepy.p99 && sleepy.p99 <= sleepy.max);
+        assert!(sleepy.min <= sleepy.mean && sleepy.mean <= sleepy.max);
+        assert!(sleepy.total - sleepy.mean * 3 < std::time::Duration::from_nanos(3));
+
+        let inc = fabric.stats("inc").unwrap();
+        assert_eq!(inc.calls, 2);
+        assert_eq!((inc.mismatches, inc.panics), (1, 0));
+        assert_eq!(fabric.stats("missing"), None);
+    }
+
+    #[test]
+    fn test_callback_panics_are_counted_and_propagated() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback("boom".to_string(), || panic!("boom"));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fabric.execute_callback("boom")));
+        assert!(result.is_err());
+
+        let stats = fabric.stats("boom").unwrap();
+        assert_eq!(stats.calls, 1);
+        assert_eq!(stats.panics, 1);
+
+        fabric.reset_stats();
+        assert_eq!(fabric.stats("boom").unwrap().calls, 0);
+    }
+
+    #[test]
+    fn test_stats_prometheus() {
+        let mut fabric = Fabric::new();
+        fabric.add_callback("db.init".to_string(), || {});
+        fabric.add_callback("unused".to_string(), || {});
+        fabric.execute_callback("db.init");
+
+        let text = fabric.stats_prometheus();
+        assert!(text.contains("# TYPE fabric_callback_calls_total counter\n"));
+        assert!(text.contains("fabric_callback_calls_total{callback=\"db.init\"} 1\n"));
+        assert!(text.contains("fabric_callback_panics_total{callback=\"db.init\"} 0\n"));
+        assert!(text.contains("# TYPE fabric_callback_duration_seconds summary\n"));
+        assert!(text.contains("fabric_callback_duration_seconds{callback=\"db.init\",quantile=\"0.99\"} "));
+        assert!(text.contains("fabric_callback_duration_seconds_count{callback=\"db.init\"} 1\n"));
+        assert!(!text.contains("unused"));
+    }
+
+    type Inputs = (Vec<i32>, HashMap<KwargsKey, KwargsValue>);
+
+    #[test]
+    fn test_functor_callbacks_use_the_cache() {
+        let calls = Calls::default();
+        let square = SyncF::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * args[0]),
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut fabric = Fabric::new();
+        let handle = fabric.add_sync_functor("square".to_string(), square);
+        let result: Option<i32> =
+            fabric.execute_callback_with_args("square", (vec![4], HashMap::new()) as Inputs);
+        assert_eq!(result, Some(16));
+        let result: Option<i32> =
+            fabric.execute_callback_with_args("square", (vec![4], HashMap::new()) as Inputs);
+        assert_eq!(result, Some(16));
+        assert_eq!(calls.get(), 1);
+
+        // The handle and the Fabric share one cache
+        assert_eq!(handle.call(vec![4], HashMap::new()), 16);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(fabric.functor_cache_stats("square").unwrap().hits, 2);
+    }
+
+    #[test]
+    fn test_invalidate_functor_through_fabric() {
+        let calls = Calls::default();
+        let double = SyncF::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * 2),
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut fabric = Fabric::new();
+        fabric.add_sync_functor("double".to_string(), double);
+        let run = |fabric: &Fabric| -> Option<i32> {
+            fabric.execute_callback_with_args("double", (vec![5], HashMap::new()) as Inputs)
+        };
+        run(&fabric);
+        assert!(fabric.invalidate_functor("double"));
+        assert_eq!(fabric.functor_cache_stats("double").unwrap().size, 0);
+        assert_eq!(run(&fabric), Some(10));
+        assert_eq!(calls.get(), 2);
+
+        assert!(!fabric.invalidate_functor("missing"));
+        fabric.remove_callback("double");
+        assert!(!fabric.invalidate_functor("double"));
+        assert_eq!(fabric.functor_cache_stats("double"), None);
+    }
+
+    #[test]
+    fn test_functor_callbacks_run_in_parallel() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (active, peak) = (running.clone(), most.clone());
+        let slow = SyncF::new(
+            move |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                active.fetch_sub(1, Ordering::SeqCst);
+                args[0]
+            },
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut fabric = Fabric::new();
+        fabric.add_sync_functor("slow".to_string(), slow);
+        std::thread::scope(|scope| {
+            for arg in 0..4 {
+                let fabric = &fabric;
+                scope.spawn(move || {
+                    let result: Option<i32> =
+                        fabric.execute_callback_with_args("slow", (vec![arg], HashMap::new()) as Inputs);
+                    assert_eq!(result, Some(arg));
+                });
+            }
+        });
+        // Calls with different keys do not wait on each other
+        assert!(most.load(Ordering::SeqCst) > 1);
+        assert_eq!(fabric.functor_cache_stats("slow").unwrap().misses, 4);
+    }
+
+    #[test]
+    fn test_register_and_invalidate_functor() {
+        let calls = Calls::default();
+        let square = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * args[0]),
+            vec![],
+            HashMap::new(),
+        )
+        .cache_policy(CachePolicy::Lru(2))
+        .named("square");
+
+        let mut fabric = Fabric::new();
+        let handle = fabric.add_functor("square".to_string(), square);
+        let run = |fabric: &Fabric, n: i32| -> Option<i32> {
+            fabric.execute_callback_with_args("square", (vec![n], HashMap::new()) as Inputs)
+        };
+        assert_eq!(run(&fabric, 3), Some(9));
+        assert_eq!(run(&fabric, 3), Some(9));
+        assert_eq!(handle.lock().unwrap().call(vec![3], HashMap::new()), 9);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(fabric.functor_cache_stats("square").unwrap().capacity, Some(2));
+
+        assert!(fabric.invalidate_functor("square"));
+        assert_eq!(handle.lock().unwrap().cache_len(), 0);
+        assert_eq!(run(&fabric, 3), Some(9));
+        assert_eq!(calls.get(), 2);
+    }
 }
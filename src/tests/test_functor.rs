@@ -1,52 +1,145 @@
-// Type alias for convenience
-
-type TestResult = String;
-
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-    use super::*;
-    use crate::rllt::functor::{F, KwargsKey, KwargsValue};
+    use std::cell::Cell;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use crate::rllt::cache::{CachePolicy, ErrorCaching};
+    use crate::rllt::call_log::{CacheStatus, LogLevel, MemorySink};
+    use crate::rllt::clock::MockClock;
+    use crate::rllt::disk_cache::DiskCache;
+    use crate::rllt::functor::{Backoff, Elapsed, F, KeyFn, KwargsKey, KwargsValue};
+    use crate::rllt::json::{FromJson, Json, ToJson};
+    use crate::rllt::kwargs::{KwargsError, KwargsExt};
+    use crate::rllt::signature::{KwargType, Signature, SignatureError};
+    use crate::tests::helpers::{counted, scratch, Calls};
+
+    // Type alias for convenience
+    type TestResult = i32;
+
     // Mock functions for testing
     fn add_one(args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>) -> TestResult {
-        (args[0] + 1).to_string()
+        args[0] + 1
     }
 
     fn multiply(args: Vec<i32>, kwargs: HashMap<KwargsKey, KwargsValue>) -> TestResult {
-        (args[0] * kwargs.values().next().unwrap().parse::<i32>().unwrap()).to_string()
+        args[0] * kwargs.get_as::<i32>("factor").unwrap()
     }
 
     fn double(args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>) -> TestResult {
-        (args[0] * 2).to_string()
+        args[0] * 2
     }
 
     fn square(args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>) -> TestResult {
-        (args[0] * args[0]).to_string()
+        args[0] * args[0]
     }
 
     fn is_even(args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>) -> bool {
         args[0] % 2 == 0
     }
 
+    fn describe(args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>) -> String {
+        format!("{} args", args.len())
+    }
+
+    fn sum_scaled(args: Vec<i32>, kwargs: HashMap<KwargsKey, KwargsValue>) -> Vec<i32> {
+        let scale = kwargs.get_or("scale", 1).unwrap();
+        let offset = kwargs.get_or("offset", 0).unwrap();
+        args.iter().map(|a| a * scale + offset).collect()
+    }
+
+    fn arg_sets(values: &[i32]) -> Vec<(Vec<i32>, HashMap<KwargsKey, KwargsValue>)> {
+        values.iter().map(|v| (vec![*v], HashMap::new())).collect()
+    }
+
     // Test cases
     #[test]
     fn test_call() {
         let mut f = F::new(add_one, vec![1], HashMap::new());
-        assert_eq!(f.call(vec![], HashMap::new()), "2");
+        assert_eq!(f.call(vec![], HashMap::new()), 2);
+    }
+
+    #[test]
+    fn test_call_with_kwargs() {
+        let mut f = F::new(multiply, vec![3], HashMap::new());
+        assert_eq!(f.call(vec![], kwargs!("factor" => 4)), 12);
+    }
+
+    #[test]
+    fn test_non_numeric_result() {
+        let mut f = F::new(describe, vec![1, 2], HashMap::new());
+        assert_eq!(f.call(vec![3], HashMap::new()), "3 args");
     }
 
     #[test]
     fn test_chain() {
-        let mut f = F::new(add_one, vec![1], HashMap::new());
+        let f = F::new(add_one, vec![1], HashMap::new());
         let mut chained = f.chain(double);
-        assert_eq!(chained.call(vec![], HashMap::new()), "4");
+        assert_eq!(chained.call(vec![], HashMap::new()), 4);
     }
 
     #[test]
     fn test_map() {
-        let mut f = F::new(add_one, vec![1], HashMap::new());
+        let f = F::new(add_one, vec![1], HashMap::new());
         let mut mapped = f.map(square);
-        assert_eq!(mapped.call(vec![], HashMap::new()), "4");
+        assert_eq!(mapped.call(vec![], HashMap::new()), 4);
+    }
+
+    #[test]
+    fn test_chain_is_lazy_and_reusable() {
+        let calls = Calls::default();
+        let f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] + 1),
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut pipeline = f.chain(double).map(describe).curry(vec![], HashMap::new());
+        assert_eq!(calls.get(), 0);
+        assert_eq!(pipeline.call(vec![1], HashMap::new()), "1 args");
+
+        let mut pipeline = f.chain(double).chain(square);
+        assert_eq!(pipeline.call(vec![1], HashMap::new()), 16);
+        assert_eq!(pipeline.call(vec![2], HashMap::new()), 36);
+        assert_eq!(pipeline.call(vec![1], HashMap::new()), 16);
+        assert_eq!(pipeline.cache_stats().hits, 1);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_chain_forwards_kwargs() {
+        let f = F::new(add_one, vec![], kwargs!("scale" => 2));
+        let mut chained = f.chain(sum_scaled);
+        let mut mapped = f.map(sum_scaled);
+
+        assert_eq!(chained.call(vec![1], HashMap::new()), vec![4]);
+        assert_eq!(chained.call(vec![1], kwargs!("offset" => 1)), vec![5]);
+        assert_eq!(mapped.call(vec![1], kwargs!("offset" => 1)), vec![2]);
+    }
+
+    #[test]
+    fn test_then_memoizes_each_stage() {
+        let calls = Calls::default();
+        let next = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * 10),
+            vec![],
+            HashMap::new(),
+        );
+        let f = F::new(|args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] % 3, vec![], HashMap::new());
+        let mut pipeline = f.then(next);
+
+        // 1 and 4 miss the pipeline cache but share the second stage's entry
+        assert_eq!(pipeline.call(vec![1], HashMap::new()), 10);
+        assert_eq!(pipeline.call(vec![4], HashMap::new()), 10);
+        assert_eq!(pipeline.cache_len(), 2);
+        assert_eq!(calls.get(), 1);
+
+        // Clones of a pipeline share their stages
+        let mut clone = pipeline.curry(vec![], HashMap::new());
+        clone.clear_cache();
+        assert_eq!(clone.call(vec![7], HashMap::new()), 10);
+        assert_eq!(calls.get(), 1);
     }
 
     #[test]
@@ -58,16 +151,55 @@ mod tests {
 
     #[test]
     fn test_curry() {
-        let mut f = F::new(add_one, vec![1], HashMap::new());
+        let f = F::new(add_one, vec![1], HashMap::new());
         let mut curried = f.curry(vec![], HashMap::new());
-        assert_eq!(curried.call(vec![], HashMap::new()), "2");
+        assert_eq!(curried.call(vec![], HashMap::new()), 2);
+    }
+
+    #[test]
+    fn test_curry_binds_args_in_order() {
+        let f = F::new(sum_scaled, vec![1], HashMap::new());
+        let mut curried = f.curry(vec![2, 3], HashMap::new()).curry(vec![4], HashMap::new());
+
+        assert_eq!(curried.call(vec![5], HashMap::new()), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_curry_kwargs_precedence() {
+        let f = F::new(sum_scaled, vec![1], kwargs!("scale" => 2));
+        let curried = f.curry(vec![], kwargs!("scale" => 3, "offset" => 1));
+
+        // Curried kwargs override the bound ones
+        assert_eq!(curried.curry(vec![], HashMap::new()).call(vec![], HashMap::new()), vec![4]);
+        // Call-time kwargs override both
+        let mut curried = curried.curry(vec![], HashMap::new());
+        assert_eq!(curried.call(vec![], kwargs!("scale" => 10)), vec![11]);
+        // The original functor is unchanged
+        assert_eq!(f.curry(vec![], HashMap::new()).call(vec![], HashMap::new()), vec![2]);
+    }
+
+    #[test]
+    fn test_curry_keeps_cache() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args.iter().sum::<i32>()),
+            vec![],
+            HashMap::new(),
+        );
+        assert_eq!(f.call(vec![1, 2], HashMap::new()), 3);
+
+        let mut curried = f.curry(vec![1], HashMap::new());
+        assert_eq!(curried.call(vec![2], HashMap::new()), 3);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(curried.call(vec![3], HashMap::new()), 4);
+        assert_eq!(calls.get(), 2);
     }
 
     #[test]
     fn test_transform_args() {
         let mut f = F::new(add_one, vec![1], HashMap::new());
         let mut transformed = f.transform_args(|args, _| (vec![args[0] * 2], HashMap::new()));
-        assert_eq!(transformed.call(vec![], HashMap::new()), "4");
+        assert_eq!(transformed.call(vec![], HashMap::new()), (vec![4], HashMap::new()));
     }
 
     #[test]
@@ -77,4 +209,755 @@ mod tests {
         f.clear_cache();
         assert!(f.cache.is_empty());
     }
+
+    #[test]
+    fn test_cache_ignores_kwargs_order() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, kwargs: HashMap<KwargsKey, KwargsValue>| args.len() + kwargs.len()),
+            vec![],
+            HashMap::new(),
+        );
+
+        let mut forward = HashMap::new();
+        forward.insert("a".to_string(), KwargsValue::Int(1));
+        forward.insert("b".to_string(), KwargsValue::Int(2));
+        let mut backward = HashMap::new();
+        backward.insert("b".to_string(), KwargsValue::Int(2));
+        backward.insert("a".to_string(), KwargsValue::Int(1));
+
+        assert_eq!(f.call(vec![1], forward), 3);
+        assert_eq!(f.call(vec![1], backward), 3);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_custom_key_fn_for_unhashable_args() {
+        let calls = Calls::default();
+        let key_fn: KeyFn<f64, f64> = Arc::new(|args: &[f64], kwargs: &[(KwargsKey, f64)]| {
+            let bits = args.iter().chain(kwargs.iter().map(|(_, v)| v)).map(|x| x.to_bits());
+            bits.fold(0u64, |acc, b| acc.rotate_left(7) ^ b)
+        });
+        let mut f = F::with_key_fn(
+            counted(&calls, |args: Vec<f64>, _: HashMap<KwargsKey, f64>| args.iter().sum::<f64>()),
+            vec![0.5],
+            HashMap::new(),
+            key_fn,
+        );
+
+        assert_eq!(f.call(vec![1.25], HashMap::new()), 1.75);
+        assert_eq!(f.call(vec![1.25], HashMap::new()), 1.75);
+        assert_eq!(f.call(vec![2.0], HashMap::new()), 2.5);
+        assert_eq!(calls.get(), 2);
+
+        // NaN != NaN, so NaN arguments never hit and each call adds an entry
+        assert!(f.call(vec![f64::NAN], HashMap::new()).is_nan());
+        assert!(f.call(vec![f64::NAN], HashMap::new()).is_nan());
+        assert_eq!((calls.get(), f.cache_len()), (4, 4));
+    }
+
+    #[test]
+    fn test_colliding_keys_are_not_confused() {
+        let key_fn: KeyFn<i32, KwargsValue> = Arc::new(|_: &[i32], _: &[(KwargsKey, KwargsValue)]| 0);
+        let mut f = F::with_key_fn(add_one, vec![], HashMap::new(), key_fn);
+
+        assert_eq!(f.call(vec![1], HashMap::new()), 2);
+        assert_eq!(f.call(vec![5], HashMap::new()), 6);
+        assert_eq!(f.cache_len(), 2);
+    }
+
+    #[test]
+    fn test_bounded_cache_policy() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * 10),
+            vec![],
+            HashMap::new(),
+        )
+        .cache_policy(CachePolicy::Lru(2));
+
+        assert_eq!(f.cache_capacity(), Some(2));
+        f.call(vec![1], HashMap::new());
+        f.call(vec![2], HashMap::new());
+        f.call(vec![1], HashMap::new());
+        f.call(vec![3], HashMap::new());
+        assert_eq!(f.cache_len(), 2);
+        assert_eq!(calls.get(), 3);
+
+        // 2 was least recently used and got evicted, 1 is still cached
+        f.call(vec![1], HashMap::new());
+        assert_eq!(calls.get(), 3);
+        f.call(vec![2], HashMap::new());
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn test_unbounded_cache_by_default() {
+        let mut f = F::new(add_one, vec![], HashMap::new());
+        for i in 0..100 {
+            f.call(vec![i], HashMap::new());
+        }
+        assert_eq!(f.cache_capacity(), None);
+        assert_eq!(f.cache_len(), 100);
+    }
+
+    #[test]
+    fn test_cache_ttl_expiry() {
+        let calls = Calls::default();
+        let clock = Arc::new(MockClock::from_unix_secs(1_000));
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0]),
+            vec![7],
+            HashMap::new(),
+        )
+        .cache_ttl(Duration::from_secs(60))
+        .clock(clock.clone());
+
+        f.call(vec![], HashMap::new());
+        clock.advance(Duration::from_secs(59));
+        f.call(vec![], HashMap::new());
+        assert_eq!(calls.get(), 1);
+
+        clock.advance(Duration::from_secs(1));
+        f.call(vec![], HashMap::new());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_cache_ttl_refresh_on_access() {
+        let calls = Calls::default();
+        let clock = Arc::new(MockClock::from_unix_secs(1_000));
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0]),
+            vec![7],
+            HashMap::new(),
+        )
+        .clock(clock.clone())
+        .cache_ttl_refreshing(Duration::from_secs(60));
+
+        f.call(vec![], HashMap::new());
+        for _ in 0..5 {
+            clock.advance(Duration::from_secs(45));
+            f.call(vec![], HashMap::new());
+        }
+        assert_eq!(calls.get(), 1);
+
+        clock.advance(Duration::from_secs(60));
+        f.call(vec![], HashMap::new());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let clock = Arc::new(MockClock::from_unix_secs(1_000));
+        let mut f = F::new(add_one, vec![], HashMap::new())
+            .cache_policy(CachePolicy::Lru(10))
+            .cache_ttl(Duration::from_secs(10))
+            .clock(clock.clone());
+
+        f.call(vec![1], HashMap::new());
+        clock.advance(Duration::from_secs(5));
+        f.call(vec![2], HashMap::new());
+        clock.advance(Duration::from_secs(5));
+
+        assert_eq!(f.purge_expired(), 1);
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let mut f = F::new(add_one, vec![], HashMap::new()).cache_policy(CachePolicy::Fifo(2));
+
+        f.call(vec![1], HashMap::new());
+        f.call(vec![1], HashMap::new());
+        f.call(vec![2], HashMap::new());
+        f.call(vec![3], HashMap::new());
+        f.call(vec![3], HashMap::new());
+
+        let stats = f.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 3, 1));
+        assert_eq!(stats.size, 2);
+        assert_eq!(stats.capacity, Some(2));
+        assert_eq!(stats.hit_ratio(), 0.4);
+
+        f.reset_cache_stats();
+        assert_eq!(f.cache_stats().hits, 0);
+        assert_eq!(f.cache_stats().size, 2);
+    }
+
+    #[test]
+    fn test_cache_entries() {
+        let mut f = F::new(multiply, vec![2], HashMap::new());
+        f.call(vec![], kwargs!("factor" => 3));
+        f.call(vec![], kwargs!("factor" => 5));
+
+        let mut entries: Vec<(Vec<i32>, String, i32)> = f
+            .cache_entries()
+            .map(|(args, kwargs, result)| (args.to_vec(), kwargs[0].1.to_string(), *result))
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec![(vec![2], "3".to_string(), 6), (vec![2], "5".to_string(), 10)]);
+    }
+
+    #[test]
+    fn test_invalidate_single_entry() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args.iter().sum::<i32>()),
+            vec![1],
+            HashMap::new(),
+        );
+        f.call(vec![2], HashMap::new());
+        f.call(vec![3], HashMap::new());
+
+        assert!(f.invalidate(vec![2], HashMap::new()));
+        assert!(!f.invalidate(vec![2], HashMap::new()));
+        assert_eq!(f.cache_len(), 1);
+
+        f.call(vec![3], HashMap::new());
+        assert_eq!(calls.get(), 2);
+        f.call(vec![2], HashMap::new());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_expirations_are_counted() {
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut f = F::new(add_one, vec![], HashMap::new())
+            .cache_ttl(Duration::from_secs(1))
+            .clock(clock.clone());
+
+        f.call(vec![1], HashMap::new());
+        f.call(vec![2], HashMap::new());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(f.cache_entries().count(), 0);
+        f.call(vec![1], HashMap::new());
+        f.purge_expired();
+
+        let stats = f.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.expirations), (0, 3, 2));
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut f = F::new(square, vec![], HashMap::new());
+
+        let sum = f.reduce(arg_sets(&[1, 2, 3, 2]), |acc, r| acc + r, 0);
+        assert_eq!(sum, 18);
+        assert_eq!(f.cache_stats().hits, 1);
+
+        // The accumulator does not have to share the result type
+        let joined = f.reduce(arg_sets(&[3, 1]), |acc: String, r| format!("{}{};", acc, r), String::new());
+        assert_eq!(joined, "9;1;");
+        assert_eq!(f.reduce(Vec::new(), |acc, r| acc + r, 7), 7);
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut f = F::new(double, vec![], HashMap::new());
+
+        let partial_sums: Vec<i32> = f.scan(arg_sets(&[1, 2, 3]), |acc, r| acc + r, 0).collect();
+        assert_eq!(partial_sums, vec![2, 6, 12]);
+
+        // Lazy: only the consumed argument sets are evaluated
+        f.clear_cache();
+        let first: Vec<i64> = f.scan(arg_sets(&[4, 5, 6]), |acc: i64, r| acc * r as i64, 1).take(2).collect();
+        assert_eq!(first, vec![8, 80]);
+        assert_eq!(f.cache_len(), 2);
+    }
+
+    #[test]
+    fn test_persist_survives_restart() {
+        let path = scratch("functor-persist");
+        let calls = Calls::default();
+        let slow_square = counted(&calls, |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] * args[0]);
+
+        let disk = Arc::new(DiskCache::directory(&path, "v1").unwrap());
+        let mut f = F::new(slow_square.clone(), vec![], HashMap::new()).persist(disk.clone());
+        assert_eq!(f.call(vec![3], HashMap::new()), 9);
+        assert_eq!(disk.len(), 1);
+
+        // A new functor, as after a restart, reads the stored result
+        let mut f = F::new(slow_square.clone(), vec![], HashMap::new()).persist(disk);
+        assert_eq!(f.call(vec![3], HashMap::new()), 9);
+        assert_eq!(calls.get(), 1);
+        assert!(f.invalidate(vec![3], HashMap::new()));
+        assert_eq!(f.call(vec![3], HashMap::new()), 9);
+        assert_eq!(calls.get(), 2);
+
+        // Bumping the version recomputes
+        let disk = Arc::new(DiskCache::directory(&path, "v2").unwrap());
+        let mut f = F::new(slow_square, vec![], HashMap::new()).persist(disk);
+        assert_eq!(f.call(vec![3], HashMap::new()), 9);
+        assert_eq!(calls.get(), 3);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_non_finite_args_are_not_persisted() {
+        let path = scratch("functor-non-finite");
+        let calls = Calls::default();
+        let show = counted(&calls, |args: Vec<KwargsValue>, _: HashMap<KwargsKey, KwargsValue>| args[0].to_string());
+        let disk = Arc::new(DiskCache::directory(&path, "v1").unwrap());
+
+        // NaN and inf both encode as null, so a stored NaN result must not answer inf
+        let mut f = F::new(show.clone(), vec![], HashMap::new()).persist(disk.clone());
+        assert_eq!(f.call(vec![KwargsValue::Float(f64::NAN)], HashMap::new()), "NaN");
+        let mut f = F::new(show, vec![], HashMap::new()).persist(disk.clone());
+        assert_eq!(f.call(vec![KwargsValue::Float(f64::INFINITY)], HashMap::new()), "inf");
+        assert_eq!((calls.get(), disk.len()), (2, 0));
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_typed_kwargs() {
+        let kwargs = kwargs!("n" => 3, "ratio" => 0.5, "flag" => true, "name" => "x", "sizes" => vec![1, 2]);
+
+        assert_eq!(kwargs.get_as::<i64>("n"), Ok(3));
+        assert_eq!(kwargs.get_as::<f64>("n"), Ok(3.0));
+        assert_eq!(kwargs.get_as::<f64>("ratio"), Ok(0.5));
+        assert_eq!(kwargs.get_as::<bool>("flag"), Ok(true));
+        assert_eq!(kwargs.get_as::<String>("name"), Ok("x".to_string()));
+        assert_eq!(kwargs.get_as::<Vec<usize>>("sizes"), Ok(vec![1, 2]));
+        assert_eq!(kwargs.get_or("missing", 7), Ok(7));
+        assert_eq!(kwargs.get_opt::<bool>("missing"), Ok(None));
+
+        assert_eq!(kwargs.get_as::<i32>("missing"), Err(KwargsError::Missing { key: "missing".to_string() }));
+        let error = kwargs.get_or("name", 0i64).unwrap_err();
+        assert_eq!(error.to_string(), "keyword argument 'name' must be an int, got str \"x\"");
+    }
+
+    #[test]
+    fn test_float_kwargs_are_cache_keys() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, |args: Vec<i32>, kwargs: HashMap<KwargsKey, KwargsValue>| args[0] as f64 * kwargs.get_as::<f64>("scale").unwrap()),
+            vec![],
+            HashMap::new(),
+        );
+
+        assert_eq!(f.call(vec![2], kwargs!("scale" => 1.5)), 3.0);
+        assert_eq!(f.call(vec![2], kwargs!("scale" => 1.5)), 3.0);
+        assert_eq!(f.call(vec![2], kwargs!("scale" => 2)), 4.0);
+        assert_eq!(calls.get(), 2);
+    }
+
+    fn scaled(args: Vec<i32>, kwargs: HashMap<KwargsKey, KwargsValue>) -> i32 {
+        let scale = kwargs.get_as::<i32>("scale").unwrap();
+        args.iter().sum::<i32>() * scale + kwargs.get_as::<i32>("offset").unwrap()
+    }
+
+    fn scaled_signature() -> Signature {
+        Signature::new()
+            .args_between(1, Some(2))
+            .required("scale", KwargType::Int)
+            .optional("offset", KwargType::Int, 0)
+    }
+
+    #[test]
+    fn test_signature_rejects_bad_inputs() {
+        let mut f = F::new(scaled, vec![], HashMap::new()).signature(scaled_signature());
+
+        assert_eq!(f.call(vec![2, 3], kwargs!("scale" => 2)), Ok(10));
+        assert_eq!(
+            f.call(vec![], kwargs!("scale" => 2)),
+            Err(SignatureError::Arity { min: 1, max: Some(2), found: 0 })
+        );
+        assert_eq!(f.call(vec![1], HashMap::new()), Err(SignatureError::MissingKwarg { name: "scale".to_string() }));
+        assert_eq!(
+            f.call(vec![1], kwargs!("scale" => "2")),
+            Err(SignatureError::KwargType { name: "scale".to_string(), expected: KwargType::Int, found: "str" })
+        );
+        let error = f.call(vec![1], kwargs!("scale" => 2, "ofset" => 1)).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected keyword argument 'ofset'");
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_signature_counts_bound_inputs_and_fills_defaults() {
+        let calls = Calls::default();
+        let mut f = F::new(
+            counted(&calls, scaled),
+            vec![1],
+            kwargs!("scale" => 3),
+        )
+        .signature(scaled_signature());
+
+        assert_eq!(f.call(vec![1], HashMap::new()), Ok(6));
+        // The default was filled in before the lookup, so this is the same call
+        assert_eq!(f.call(vec![1], kwargs!("offset" => 0)), Ok(6));
+        assert_eq!(calls.get(), 1);
+        assert!(matches!(f.call(vec![1, 1], HashMap::new()), Err(SignatureError::Arity { found: 3, .. })));
+    }
+
+    #[test]
+    fn test_pipeline_checks_first_stage_signature() {
+        let f = F::new(scaled, vec![1], HashMap::new()).signature(scaled_signature());
+        let mut pipeline = f.map(double);
+
+        assert_eq!(pipeline.call(vec![], kwargs!("scale" => 5)), Ok(10));
+        assert_eq!(
+            pipeline.call(vec![2, 3], kwargs!("scale" => 5)),
+            Err(SignatureError::Arity { min: 1, max: Some(2), found: 3 })
+        );
+        assert!(pipeline.call(vec![], HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_pipeline_checks_every_stage_before_running() {
+        let calls = Calls::default();
+        let first = F::new(counted(&calls, add_one), vec![], HashMap::new());
+        let next = F::new(scaled, vec![], kwargs!("offset" => 1)).signature(scaled_signature());
+        let mut pipeline = first.then(next);
+
+        // The second stage gets the first one's result and the pipeline's kwargs
+        assert_eq!(pipeline.call(vec![1], kwargs!("scale" => 3)), Ok(7));
+        assert_eq!(pipeline.call(vec![1], HashMap::new()), Err(SignatureError::MissingKwarg { name: "scale".to_string() }));
+        assert_eq!(
+            pipeline.call(vec![1], kwargs!("scale" => 3, "x" => 1)),
+            Err(SignatureError::UnknownKwarg { name: "x".to_string() })
+        );
+        assert_eq!(calls.get(), 1);
+
+        // Stages of a stage are checked too, with the args the inner pipeline passes them
+        let arity = Signature::new().args(1);
+        let inner = F::new(add_one, vec![], HashMap::new()).then(F::new(double, vec![5], HashMap::new()).signature(arity.clone()));
+        let mut outer = inner.then(F::new(add_one, vec![], HashMap::new()).signature(arity));
+        assert_eq!(outer.call(vec![1], HashMap::new()), Err(SignatureError::Arity { min: 1, max: Some(1), found: 2 }));
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum ParseError {
+        Invalid(String),
+        Signature(SignatureError),
+    }
+
+    impl From<SignatureError> for ParseError {
+        fn from(error: SignatureError) -> Self {
+            ParseError::Signature(error)
+        }
+    }
+
+    fn parse_first(args: Vec<String>, _: HashMap<KwargsKey, KwargsValue>) -> Result<i32, ParseError> {
+        args[0].parse().map_err(|_| ParseError::Invalid(args[0].clone()))
+    }
+
+    #[test]
+    fn test_error_caching() {
+        let calls = Calls::default();
+        let input = || vec!["x".to_string()];
+
+        let mut cached = F::new(counted(&calls, parse_first), vec![], HashMap::new());
+        cached.call(input(), HashMap::new()).unwrap_err();
+        cached.call(input(), HashMap::new()).unwrap_err();
+        assert_eq!(calls.get(), 1);
+
+        let calls = Calls::default();
+        let mut skipped = F::new(counted(&calls, parse_first), vec![], HashMap::new()).error_caching(ErrorCaching::Skip);
+        skipped.call(input(), HashMap::new()).unwrap_err();
+        skipped.call(input(), HashMap::new()).unwrap_err();
+        assert_eq!(skipped.call(vec!["4".to_string()], HashMap::new()), Ok(4));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(skipped.cache_len(), 1);
+
+        let calls = Calls::default();
+        let clock = Arc::new(MockClock::from_unix_secs(0));
+        let mut short = F::new(counted(&calls, parse_first), vec![], HashMap::new())
+            .error_caching(ErrorCaching::Ttl(Duration::from_secs(5)))
+            .clock(clock.clone());
+        short.call(input(), HashMap::new()).unwrap_err();
+        short.call(vec!["4".to_string()], HashMap::new()).unwrap();
+        clock.advance(Duration::from_secs(5));
+        short.call(input(), HashMap::new()).unwrap_err();
+        short.call(vec!["4".to_string()], HashMap::new()).unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_try_chain_short_circuits() {
+        let calls = Calls::default();
+        let f = F::new(parse_first, vec![], HashMap::new()).error_caching(ErrorCaching::Skip);
+        let mut pipeline = f
+            .try_chain(counted(&calls, |values: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                if values[0] == 0 {
+                    Err(ParseError::Invalid("zero".to_string()))
+                } else {
+                    Ok(100 / values[0])
+                }
+            }))
+            .try_map(|values: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| values[0] + 1);
+
+        assert_eq!(pipeline.try_call(vec!["4".to_string()], HashMap::new()), Ok(26));
+        assert_eq!(pipeline.try_call(vec!["0".to_string()], HashMap::new()), Err(ParseError::Invalid("zero".to_string())));
+        assert_eq!(pipeline.try_call(vec!["y".to_string()], HashMap::new()), Err(ParseError::Invalid("y".to_string())));
+        assert_eq!(calls.get(), 2);
+        // Errors were not cached by the pipeline either
+        assert_eq!(pipeline.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_try_call_reports_signature_errors() {
+        let mut f = F::new(parse_first, vec![], HashMap::new()).signature(Signature::new().args(1));
+
+        assert_eq!(f.try_call(vec!["7".to_string()], HashMap::new()), Ok(7));
+        assert_eq!(
+            f.try_call(vec![], HashMap::new()),
+            Err(ParseError::Signature(SignatureError::Arity { min: 1, max: Some(1), found: 0 }))
+        );
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum FetchError {
+        Flaky,
+        TimedOut,
+    }
+
+    impl From<Elapsed> for FetchError {
+        fn from(_: Elapsed) -> Self {
+            FetchError::TimedOut
+        }
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let millis = Duration::from_millis;
+        assert_eq!(Backoff::None.delay(3), Duration::ZERO);
+        assert_eq!(Backoff::Fixed(millis(5)).delay(3), millis(5));
+        let exponential = Backoff::Exponential { initial: millis(10), max: millis(50) };
+        assert_eq!(exponential.delay(1), millis(10));
+        assert_eq!(exponential.delay(3), millis(40));
+        assert_eq!(exponential.delay(4), millis(50));
+        assert_eq!(exponential.delay(100), millis(50));
+    }
+
+    #[test]
+    fn test_retry_caches_only_success() {
+        let calls = Cell::new(0);
+        // Fails until it has been called three times
+        let flaky = |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(FetchError::Flaky)
+            } else {
+                Ok(args[0])
+            }
+        };
+        let f = F::new(flaky, vec![], HashMap::new());
+
+        let mut once = f.retry(1, Backoff::None);
+        assert_eq!(once.call(vec![1], HashMap::new()), Err(FetchError::Flaky));
+        assert_eq!(once.cache_len(), 0);
+
+        calls.set(0);
+        let mut patient = f.retry(2, Backoff::Fixed(Duration::from_millis(1)));
+        assert_eq!(patient.call(vec![1], HashMap::new()), Ok(1));
+        assert_eq!(patient.call(vec![1], HashMap::new()), Ok(1));
+        assert_eq!(calls.get(), 3);
+    }
+
+    impl ToJson for FetchError {
+        fn to_json(&self) -> Json {
+            Json::String(format!("{:?}", self))
+        }
+    }
+
+    impl FromJson for FetchError {
+        fn from_json(json: &Json) -> Option<Self> {
+            match json.as_str()? {
+                "Flaky" => Some(FetchError::Flaky),
+                "TimedOut" => Some(FetchError::TimedOut),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_ignores_errors_on_disk() {
+        let path = scratch("functor-retry");
+        let disk = Arc::new(DiskCache::directory(&path, "v1").unwrap());
+        let calls = Cell::new(0);
+        let flaky = |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(FetchError::Flaky)
+            } else {
+                Ok(args[0])
+            }
+        };
+
+        // By default errors are cached, on disk too
+        let mut f = F::new(flaky, vec![], HashMap::new()).persist(disk.clone());
+        assert_eq!(f.call(vec![1], HashMap::new()), Err(FetchError::Flaky));
+        assert_eq!(disk.len(), 1);
+
+        let mut patient = f.retry(2, Backoff::None);
+        assert_eq!(patient.call(vec![1], HashMap::new()), Ok(1));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(f.call(vec![1], HashMap::new()), Err(FetchError::Flaky));
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    fn slow_identity(args: Vec<u64>, _: HashMap<KwargsKey, KwargsValue>) -> Result<u64, FetchError> {
+        thread::sleep(Duration::from_millis(args[0]));
+        Ok(args[0])
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut f = F::new(slow_identity, vec![], HashMap::new()).timeout(Duration::from_millis(100));
+
+        assert_eq!(f.call(vec![1], HashMap::new()), Ok(1));
+        assert_eq!(f.call(vec![500], HashMap::new()), Err(FetchError::TimedOut));
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_timeout_resumes_worker_panics() {
+        let panicking = |_: Vec<u64>, _: HashMap<KwargsKey, KwargsValue>| -> Result<u64, FetchError> { panic!("worker failed") };
+        let mut f = F::new(panicking, vec![], HashMap::new()).timeout(Duration::from_secs(5));
+
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f.call(vec![1], HashMap::new()))).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"));
+    }
+
+    #[test]
+    fn test_fallback_and_composition() {
+        let failing = |_: Vec<u64>, _: HashMap<KwargsKey, KwargsValue>| -> Result<u64, FetchError> { Err(FetchError::Flaky) };
+        let mut f = F::new(failing, vec![], HashMap::new()).fallback(|args: Vec<u64>, _| Ok(args[0] + 1));
+        assert_eq!(f.call(vec![1], HashMap::new()), Ok(2));
+
+        // A timed out call falls back, and the fallback's result is cached
+        let mut f = F::new(slow_identity, vec![], HashMap::new())
+            .timeout(Duration::from_millis(50))
+            .fallback(|_: Vec<u64>, _| Ok(0));
+        assert_eq!(f.call(vec![300], HashMap::new()), Ok(0));
+        assert_eq!(f.cache_len(), 1);
+    }
+
+    #[test]
+    fn test_call_logging_levels() {
+        let sink = Arc::new(MemorySink::new());
+        let mut f = F::new(multiply, vec![3], HashMap::new()).named("multiply").log_calls(LogLevel::Full, sink.clone());
+        f.call(vec![], kwargs!("factor" => 2));
+        f.call(vec![], kwargs!("factor" => 2));
+
+        let records = sink.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].functor, "multiply");
+        assert_eq!(records[0].args, "[3]");
+        assert_eq!(records[0].kwargs, "{factor: Int(2)}");
+        assert_eq!(records[0].result.as_deref(), Some("6"));
+        assert!(records[0].duration.is_some());
+        assert_eq!((records[0].cache, records[1].cache), (CacheStatus::Miss, CacheStatus::Hit));
+
+        sink.clear();
+        let mut f = F::new(add_one, vec![], HashMap::new()).log_calls(LogLevel::Args, sink.clone());
+        f.call(vec![1], HashMap::new());
+        let records = sink.records();
+        assert!(records[0].functor.ends_with("add_one"));
+        assert_eq!((records[0].result.clone(), records[0].duration), (None, None));
+
+        sink.clear();
+        let mut f = F::new(add_one, vec![], HashMap::new()).log_calls(LogLevel::Off, sink.clone());
+        f.call(vec![1], HashMap::new());
+        assert!(sink.records().is_empty());
+    }
+
+    #[test]
+    fn test_call_logging_reports_disk_hits() {
+        let path = scratch("functor-log.jsonl");
+        let disk = Arc::new(DiskCache::file(&path, "v1").unwrap());
+        let sink = Arc::new(MemorySink::new());
+
+        F::new(square, vec![], HashMap::new()).persist(disk.clone()).call(vec![4], HashMap::new());
+        let mut f = F::new(square, vec![], HashMap::new()).persist(disk).log_calls(LogLevel::Args, sink.clone());
+        f.call(vec![4], HashMap::new());
+
+        assert_eq!(sink.records()[0].cache, CacheStatus::DiskHit);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_call_many_dedupes_and_keeps_order() {
+        let calls = Calls::default();
+        let mut f = F::new(counted(&calls, square), vec![], HashMap::new());
+        f.call(vec![2], HashMap::new());
+
+        let inputs = [3, 2, 3, 4, 3].into_iter().map(|n| (vec![n], HashMap::new()));
+        assert_eq!(f.call_many(inputs), vec![9, 4, 9, 16, 9]);
+        assert_eq!(calls.get(), 3);
+        assert_eq!(f.cache_len(), 3);
+    }
+
+    #[test]
+    fn test_call_many_parallel() {
+        let mut f = F::new(square, vec![], HashMap::new());
+        let inputs: Vec<_> = (0..100).map(|n| (vec![n % 40], HashMap::new())).collect();
+        let expected: Vec<i32> = (0..100).map(|n| (n % 40) * (n % 40)).collect();
+
+        assert_eq!(f.call_many_parallel(inputs.clone(), 4), expected.clone());
+        assert_eq!(f.cache_len(), 40);
+        assert_eq!(f.call_many_parallel(inputs, 0), expected);
+    }
+
+    #[test]
+    fn test_call_many_validates_before_computing() {
+        let mut f = F::new(square, vec![], HashMap::new()).signature(Signature::new().args(1));
+        let inputs = vec![(vec![1], HashMap::new()), (vec![1, 2], HashMap::new())];
+        assert_eq!(f.call_many(inputs), Err(SignatureError::Arity { min: 1, max: Some(1), found: 2 }));
+        assert_eq!(f.cache_len(), 0);
+    }
+
+    // The cache inside F is mutable, its identity is not
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn test_identity_of_fn_pointers() {
+        type Pointer = fn(Vec<i32>, HashMap<KwargsKey, KwargsValue>) -> TestResult;
+        let functor = |func: Pointer, bound: i32| F::new(func, vec![bound], HashMap::new());
+
+        // Without a name only the bound inputs tell functors apart
+        let unnamed = functor(add_one, 1);
+        assert!(unnamed.curry(vec![], HashMap::new()) == unnamed);
+        assert!(functor(add_one, 1) == functor(add_one, 1));
+        assert!(functor(add_one, 1) == functor(square, 1));
+        assert!(functor(add_one, 1) != functor(add_one, 2));
+        assert!(functor(add_one, 1).named("add_one") != functor(square, 1).named("square"));
+        assert!(functor(add_one, 1).named("add_one") != functor(add_one, 1));
+        assert!(functor(add_one, 1).named("add_one") != functor(add_one, 2).named("add_one"));
+        let scaled = |factor: i64| F::new(multiply as Pointer, vec![], kwargs!("factor" => factor)).named("multiply");
+        assert!(scaled(2) != scaled(3));
+        assert!(scaled(2) == scaled(2));
+
+        let set: HashSet<_> = vec![
+            functor(add_one, 1).named("add_one"),
+            functor(square, 1).named("square"),
+            functor(square, 1).named("square"),
+            functor(add_one, 1),
+            functor(square, 1)
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&functor(add_one, 1)));
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn test_identity_of_closures() {
+        let shifted = |offset: i32| {
+            let kwargs = kwargs!("a" => 1, "b" => 2, "c" => 3);
+            F::new(move |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| args[0] + offset, vec![], kwargs)
+                .named(&format!("shifted_by_{}", offset))
+        };
+
+        // Kwargs hash the same whatever order their maps iterate in
+        let mut counts = HashMap::new();
+        for f in [shifted(1), shifted(1), shifted(2), shifted(1)] {
+            *counts.entry(f).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.get(&shifted(1)), Some(&3));
+        assert!(shifted(2) == shifted(2));
+    }
 }
\ No newline at end of file
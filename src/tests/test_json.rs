@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::json::{FromJson, Json, ToJson};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        let text = r#"{"a":[1,2.5,-3e2],"b":{"c":null,"d":true},"e":"x\"y\né"}"#;
+        let value = Json::parse(text).unwrap();
+
+        assert_eq!(value.get("a").unwrap().as_array().unwrap()[2], Json::Number(-300.0));
+        assert_eq!(value.get("b").unwrap().get("d"), Some(&Json::Bool(true)));
+        assert_eq!(value.get("e").unwrap().as_str(), Some("x\"y\né"));
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(Json::parse(&value.to_string_pretty()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_pretty_output() {
+        let mut object = BTreeMap::new();
+        object.insert("list".to_string(), Json::Array(vec![Json::Number(1.0), Json::Array(Vec::new())]));
+        object.insert("name".to_string(), Json::String("x".to_string()));
+
+        assert_eq!(Json::Object(object).to_string_pretty(), "{\n  \"list\": [\n    1,\n    []\n  ],\n  \"name\": \"x\"\n}");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Json::parse("").is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("\"unterminated").is_err());
+        assert_eq!(Json::parse("[1] x").unwrap_err().offset, 4);
+    }
+
+    #[test]
+    fn test_number_grammar() {
+        assert_eq!(Json::parse("-0.5e+2"), Ok(Json::Number(-50.0)));
+        assert_eq!(Json::parse("0"), Ok(Json::Number(0.0)));
+        for text in ["1.", ".5", "-", "1e", "1e+", "+1", "01", "1.e3", "--1"] {
+            assert!(Json::parse(text).is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        assert_eq!(Json::parse(r#""\ud83d\ude00 \u00e9""#), Ok(Json::String("\u{1f600} \u{e9}".to_string())));
+        assert!(Json::parse(r#""\ud83d\u0041""#).is_err());
+        assert!(Json::parse(r#""\ud83d""#).is_err());
+        assert!(Json::parse(r#""\ude00""#).is_err());
+        assert!(Json::parse(r#""\u+123""#).is_err());
+    }
+
+    #[test]
+    fn test_long_strings() {
+        // Parsing used to rescan the rest of the document for every char
+        let long = "é".repeat(200_000);
+        let document = Json::Array(vec![Json::String(long.clone()), Json::String("x\"y".to_string())]);
+        assert_eq!(Json::parse(&document.to_string()), Ok(document));
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(128)).is_ok());
+        assert_eq!(Json::parse(&nested(129)).unwrap_err().offset, 128);
+        // Would overflow the stack without the limit
+        let error = Json::parse(&"{\"a\":".repeat(1_000_000)).unwrap_err();
+        assert_eq!(error.message, "nested too deeply");
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let values = vec![Some(1.5f64), None];
+        assert_eq!(Vec::<Option<f64>>::from_json(&values.to_json()), Some(values));
+        assert_eq!(String::from_json(&"x".to_string().to_json()), Some("x".to_string()));
+        assert_eq!(u64::from_json(&u64::MAX.to_json()), Some(u64::MAX));
+        assert_eq!(i32::from_json(&(-7i32).to_json()), Some(-7));
+        assert_eq!((-7i32).to_json(), Json::Number(-7.0));
+
+        assert_eq!(u8::from_json(&Json::Number(256.0)), None);
+        assert_eq!(i32::from_json(&Json::Number(1.5)), None);
+        assert_eq!(bool::from_json(&Json::Null), None);
+
+        let failed: Result<i32, String> = Err("x".to_string());
+        assert_eq!(failed.to_json().to_string(), r#"{"err":"x"}"#);
+        assert_eq!(Result::<i32, String>::from_json(&failed.to_json()), Some(failed));
+        assert_eq!(Result::<i32, String>::from_json(&Ok::<i32, String>(3).to_json()), Some(Ok(3)));
+        assert_eq!(Result::<i32, String>::from_json(&Json::parse(r#"{"ok":1,"err":"x"}"#).unwrap()), None);
+    }
+}
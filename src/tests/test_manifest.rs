@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::fabric::{CallbackKind, Fabric};
+    use crate::rllt::manifest::{Manifest, ManifestError, ManifestMismatch};
+
+    fn sample_fabric() -> Fabric {
+        let mut fabric = Fabric::new();
+        fabric.add_callback("db.init".to_string(), || {});
+        fabric.add_callback_with_args("len".to_string(), |s: &String| -> usize { s.len() });
+        fabric.describe("db.init", "Opens the \"main\" database");
+        fabric.tag("db.init", "owner", "storage");
+        fabric.tag("db.init", "stage", "boot");
+        fabric
+    }
+
+    #[test]
+    fn test_manifest_from_fabric() {
+        let manifest = sample_fabric().manifest();
+
+        assert_eq!(manifest.callbacks.len(), 2);
+        let db = &manifest.callbacks[0];
+        assert_eq!(db.name, "db.init");
+        assert_eq!(db.kind, CallbackKind::Void);
+        assert_eq!(db.arg_type, None);
+        assert_eq!(db.description.as_deref(), Some("Opens the \"main\" database"));
+        assert_eq!(db.tags["owner"], "storage");
+
+        let len = &manifest.callbacks[1];
+        assert_eq!(len.kind, CallbackKind::WithArgs);
+        assert_eq!(len.arg_type.as_deref(), Some(std::any::type_name::<String>()));
+        assert_eq!(len.return_type.as_deref(), Some("usize"));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let manifest = sample_fabric().manifest();
+        let json = manifest.to_json();
+
+        assert!(json.contains("\"name\": \"db.init\""));
+        assert_eq!(Manifest::from_json(&json).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let manifest = sample_fabric().manifest();
+        let toml = manifest.to_toml();
+
+        assert!(toml.starts_with("[[callback]]\nname = \"db.init\"\nkind = \"void\"\n"));
+        assert!(toml.contains("\n[callback.tags]\nowner = \"storage\"\nstage = \"boot\"\n"));
+        assert_eq!(Manifest::from_toml(&toml).unwrap(), manifest);
+
+        // Quoted keys may hold '=' and escaped quotes
+        let mut fabric = sample_fabric();
+        fabric.tag("len", "a=b \"c\"", "x = y");
+        let manifest = fabric.manifest();
+        let toml = manifest.to_toml();
+        assert!(toml.contains("\n\"a=b \\\"c\\\"\" = \"x = y\"\n"));
+        assert_eq!(Manifest::from_toml(&toml).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_validate_against_fabric() {
+        let fabric = sample_fabric();
+        assert_eq!(fabric.manifest().validate(&fabric), Ok(()));
+
+        let expected = r#"{"callbacks": [
+            {"name": "db.init", "kind": "void"},
+            {"name": "len", "kind": "with_args", "arg_type": "i32", "return_type": "usize"},
+            {"name": "cache.init", "kind": "void"}
+        ]}"#;
+        let mismatches = Manifest::from_json(expected).unwrap().validate(&fabric).unwrap_err();
+
+        assert_eq!(
+            mismatches,
+            vec![
+                ManifestMismatch::ArgType {
+                    name: "len".to_string(),
+                    expected: "i32".to_string(),
+                    found: Some(std::any::type_name::<String>().to_string()),
+                },
+                ManifestMismatch::Missing { name: "cache.init".to_string() }
+            ]
+        );
+        assert_eq!(mismatches[1].to_string(), "callback 'cache.init' is not registered");
+    }
+
+    #[test]
+    fn test_invalid_manifests() {
+        assert!(Manifest::from_json("{\"callbacks\": [{\"name\": \"x\", \"kind\": \"async\"}]}").is_err());
+        assert!(Manifest::from_json("{\"callbacks\": [{\"kind\": \"void\"}]}").is_err());
+        assert!(Manifest::from_toml("name = \"x\"").is_err());
+        assert!(Manifest::from_toml("[[callback]]\nname = x\nkind = \"void\"").is_err());
+        assert!(Manifest::from_toml("[[callback]]\n\"name = \"x\"").is_err());
+
+        assert_eq!(Manifest::from_json("{}"), Err(ManifestError::MissingCallbacks));
+        assert_eq!(Manifest::from_json("{\"callbacks\": {}}"), Err(ManifestError::MissingCallbacks));
+        let tags = Manifest::from_json("{\"callbacks\": [{\"name\": \"x\", \"kind\": \"void\", \"tags\": [\"a\"]}]}");
+        assert_eq!(tags, Err(ManifestError::Entry { index: 0, message: "'tags' must be an object".to_string() }));
+    }
+}
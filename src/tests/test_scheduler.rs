@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::clock::MockClock;
+    use crate::rllt::fabric::Fabric;
+    use crate::rllt::scheduler::{CronError, CronExpr, ScheduleError, Scheduler};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // 2024-01-01T00:00:00Z, a Monday
+    const MONDAY_MIDNIGHT: u64 = 1_704_067_200;
+
+    fn counting_fabric(name: &str) -> (Fabric, Arc<AtomicUsize>) {
+        let mut fabric = Fabric::new();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = counter.clone();
+        fabric.add_callback(name.to_string(), move || {
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        (fabric, counter)
+    }
+
+    #[test]
+    fn test_every_fires_on_each_interval() {
+        let (fabric, counter) = counting_fabric("tick");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.every("tick".to_string(), Duration::from_secs(10)).unwrap();
+
+        assert_eq!(scheduler.run_pending(), 0);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(scheduler.run_pending(), 1);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(scheduler.run_pending(), 0);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(scheduler.run_pending(), 1);
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_after_fires_once() {
+        let (fabric, counter) = counting_fabric("once");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.after("once".to_string(), Duration::from_secs(3));
+
+        clock.advance(Duration::from_secs(60));
+        scheduler.run_pending();
+        clock.advance(Duration::from_secs(60));
+        scheduler.run_pending();
+
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.time_until_next(), None);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (fabric, counter) = counting_fabric("tick");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.every("tick".to_string(), Duration::from_secs(1)).unwrap();
+        scheduler.cancel("tick");
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(scheduler.run_pending(), 0);
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_zero_interval_is_rejected() {
+        let (fabric, _) = counting_fabric("tick");
+        let mut scheduler = Scheduler::with_clock(fabric, Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT)));
+        assert_eq!(scheduler.every("tick".to_string(), Duration::ZERO), Err(ScheduleError::ZeroInterval));
+        assert_eq!(scheduler.time_until_next(), None);
+    }
+
+    #[test]
+    fn test_unregistered_callbacks_are_missed() {
+        let (fabric, counter) = counting_fabric("tick");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.every("tick".to_string(), Duration::from_secs(1)).unwrap();
+        scheduler.every("tock".to_string(), Duration::from_secs(1)).unwrap();
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(scheduler.run_pending(), 1);
+        assert_eq!(scheduler.missed(), 1);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let start = UNIX_EPOCH + Duration::from_secs(MONDAY_MIDNIGHT);
+
+        let every_quarter = CronExpr::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter.next_after(start), Some(start + Duration::from_secs(15 * 60)));
+
+        // 09:30 on weekdays, starting from Saturday 2024-01-06 lands on Monday 2024-01-08
+        let weekdays = CronExpr::parse("30 9 * * 1-5").unwrap();
+        let saturday = start + Duration::from_secs(5 * 86_400);
+        let monday = start + Duration::from_secs(7 * 86_400 + 9 * 3600 + 30 * 60);
+        assert_eq!(weekdays.next_after(saturday), Some(monday));
+
+        // A stepped day field is not a restriction, so it narrows Mondays to odd days
+        let odd_mondays = CronExpr::parse("0 0 */2 * 1").unwrap();
+        assert_eq!(odd_mondays.next_after(start), Some(start + Duration::from_secs(14 * 86_400)));
+
+        // Both day fields restricted: the 13th or any Friday, here Friday 2024-01-05
+        let either = CronExpr::parse("0 0 13 * 5").unwrap();
+        assert_eq!(either.next_after(start), Some(start + Duration::from_secs(4 * 86_400)));
+
+        // February 30th never happens
+        let never = CronExpr::parse("0 0 30 2 *").unwrap();
+        assert_eq!(never.next_after(start), None);
+    }
+
+    #[test]
+    fn test_cron_parse_errors() {
+        assert_eq!(CronExpr::parse("* * *"), Err(CronError::FieldCount(3)));
+        assert_eq!(CronExpr::parse("60 * * * *"), Err(CronError::InvalidField("60".to_string())));
+        assert_eq!(CronExpr::parse("*/0 * * * *"), Err(CronError::InvalidField("*/0".to_string())));
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let (fabric, counter) = counting_fabric("hourly");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.cron("hourly".to_string(), "0 * * * *").unwrap();
+
+        clock.advance(Duration::from_secs(30 * 60));
+        assert_eq!(scheduler.run_pending(), 0);
+        clock.advance(Duration::from_secs(30 * 60));
+        assert_eq!(scheduler.run_pending(), 1);
+        assert_eq!(scheduler.time_until_next(), Some(Duration::from_secs(3600)));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_background_thread_and_shutdown() {
+        let (fabric, counter) = counting_fabric("tick");
+        let clock = Arc::new(MockClock::from_unix_secs(MONDAY_MIDNIGHT));
+        let mut scheduler = Scheduler::with_clock(fabric, clock.clone());
+        scheduler.set_tick(Duration::from_millis(5));
+        scheduler.every("tick".to_string(), Duration::from_secs(1)).unwrap();
+
+        let handle = scheduler.start();
+        clock.advance(Duration::from_secs(1));
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while counter.load(Ordering::SeqCst) == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let mut scheduler = handle.shutdown();
+
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.time_until_next(), Some(Duration::from_secs(1)));
+
+        // The thread has been joined, due jobs now only run through the returned scheduler
+        clock.advance(Duration::from_secs(1));
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.run_pending(), 1);
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::rllt::functor::{F, KwargsKey, KwargsValue};
+    use crate::rllt::json::{FromJson, Json, ToJson};
+    use crate::rllt::signature::{KwargType, Signature, SignatureError};
+    use crate::rllt::snapshot::SnapshotError;
+
+    type Scale = fn(Vec<i64>, HashMap<KwargsKey, KwargsValue>) -> i64;
+
+    fn scale(args: Vec<i64>, kwargs: HashMap<KwargsKey, KwargsValue>) -> i64 {
+        let factor = match kwargs.get("factor") {
+            Some(KwargsValue::Int(n)) => *n,
+            _ => 1,
+        };
+        args.iter().sum::<i64>() * factor
+    }
+
+    fn panics(_: Vec<i64>, _: HashMap<KwargsKey, KwargsValue>) -> i64 {
+        panic!("should have been served from the cache")
+    }
+
+    fn scaled() -> F<Scale, i64, KwargsValue, i64> {
+        let mut f = F::new(scale as Scale, vec![], HashMap::new());
+        f.call(vec![1, 2], HashMap::new());
+        f.call(vec![3], kwargs!("factor" => 4));
+        f
+    }
+
+    #[test]
+    fn test_warm() {
+        let mut f = F::new(panics, vec![10], HashMap::new()).signature(Signature::new().optional("factor", KwargType::Int, 1));
+        let entries = vec![(vec![1], HashMap::new(), 11), (vec![2], kwargs!("factor" => 3), 36)];
+
+        assert_eq!(f.warm(entries), Ok(2));
+        assert_eq!(f.call(vec![1], kwargs!("factor" => 1)), Ok(11));
+        assert_eq!(f.call(vec![2], kwargs!("factor" => 3)), Ok(36));
+
+        let rejected = vec![(vec![3], kwargs!("scale" => 2), 0)];
+        assert_eq!(f.warm(rejected), Err(SignatureError::UnknownKwarg { name: "scale".to_string() }));
+        assert_eq!(f.cache_len(), 2);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let snapshot = scaled().export_json();
+        assert_eq!(snapshot.get("entries").unwrap().as_array().unwrap().len(), 2);
+
+        // Through text, as another process would read it
+        let snapshot = Json::parse(&snapshot.to_string()).unwrap();
+        let mut f = F::new(panics, vec![], HashMap::new());
+        assert_eq!(f.import_json(&snapshot), Ok(2));
+        assert_eq!(f.call(vec![1, 2], HashMap::new()), 3);
+        assert_eq!(f.call(vec![3], kwargs!("factor" => 4)), 12);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let text = scaled().export_csv();
+        assert!(text.starts_with("args,kwargs,result\n"));
+        assert!(text.contains("\"[3]\",\"{\"\"factor\"\":{\"\"int\"\":4}}\",\"12\"\n"));
+
+        let mut f = F::new(panics, vec![], HashMap::new());
+        assert_eq!(f.import_csv(&text.replace('\n', "\r\n")), Ok(2));
+        assert_eq!(f.call(vec![3], kwargs!("factor" => 4)), 12);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut f = F::new(panics, vec![], HashMap::new());
+
+        assert!(matches!(f.import_json(&Json::Null), Err(SnapshotError::Format(_))));
+        let wrong_type = Json::parse(r#"{"format":1,"entries":[{"args":[1],"kwargs":{},"result":2},{"args":["x"],"kwargs":{},"result":2}]}"#);
+        assert_eq!(f.import_json(&wrong_type.unwrap()), Err(SnapshotError::Entry { index: 1 }));
+        assert_eq!(f.cache_len(), 0);
+
+        assert!(matches!(f.import_csv("a,b,c\n"), Err(SnapshotError::Csv { line: 1, .. })));
+        assert!(matches!(f.import_csv("args,kwargs,result\n\"[1]\",\"{}\"\n"), Err(SnapshotError::Csv { line: 2, .. })));
+        assert!(matches!(f.import_csv("args,kwargs,result\n\"[1],{},2\n"), Err(SnapshotError::Csv { line: 2, .. })));
+        assert!(matches!(f.import_csv("args,kwargs,result\n[1,{},2\n"), Err(SnapshotError::Json(_))));
+    }
+
+    #[test]
+    fn test_kwargs_value_from_json() {
+        let value = KwargsValue::List(vec![KwargsValue::Int(2), KwargsValue::Float(0.5), "x".into(), true.into()]);
+        assert_eq!(KwargsValue::from_json(&value.to_json()), Some(value));
+        assert_eq!(KwargsValue::Float(2.0).to_json().to_string(), r#"{"float":2}"#);
+        assert_eq!(KwargsValue::from_json(&KwargsValue::Float(2.0).to_json()), Some(KwargsValue::Float(2.0)));
+        let large = KwargsValue::Int(i64::MAX);
+        assert_eq!(KwargsValue::from_json(&large.to_json()), Some(large));
+        assert_eq!(KwargsValue::from_json(&Json::parse(r#"{"int":2.5}"#).unwrap()), None);
+        assert_eq!(KwargsValue::from_json(&Json::Number(2.0)), None);
+        assert_eq!(KwargsValue::from_json(&Json::Null), None);
+    }
+
+    #[test]
+    fn test_non_finite_entries_are_not_exported() {
+        let divide = |args: Vec<i64>, _: HashMap<KwargsKey, KwargsValue>| args[0] as f64 / args[1] as f64;
+        let mut f = F::new(divide, vec![], HashMap::new());
+        f.call(vec![1, 2], HashMap::new());
+        f.call(vec![1, 0], HashMap::new());
+        f.call(vec![0, 0], HashMap::new());
+        f.call(vec![3, 1], kwargs!("scale" => f64::INFINITY));
+
+        let mut imported = F::new(|_: Vec<i64>, _: HashMap<KwargsKey, KwargsValue>| -> f64 { panic!() }, vec![], HashMap::new());
+        assert_eq!(imported.import_json(&Json::parse(&f.export_json().to_string()).unwrap()), Ok(1));
+        assert_eq!(imported.import_csv(&f.export_csv()), Ok(1));
+        assert_eq!(imported.call(vec![1, 2], HashMap::new()), 0.5);
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::fabric::Fabric;
+    use crate::rllt::state_machine::{StateMachine, TransitionError};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn door(log: &Arc<Mutex<Vec<String>>>) -> StateMachine {
+        let mut fabric = Fabric::new();
+        for name in ["closed.exit", "open.enter", "opening", "closed.enter"] {
+            let log = log.clone();
+            fabric.add_callback(name.to_string(), move || log.lock().unwrap().push(name.to_string()));
+        }
+
+        let mut machine = StateMachine::new(fabric, "closed");
+        machine.add_transition("open", "closed", "open");
+        machine.add_transition("close", "open", "closed");
+        machine.on_exit("closed", "closed.exit");
+        machine.on_transition("open", "opening");
+        machine.on_enter("open", "open.enter");
+        machine.on_enter("closed", "closed.enter");
+        machine
+    }
+
+    #[test]
+    fn test_trigger_runs_hooks_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = door(&log);
+
+        assert_eq!(machine.trigger("open"), Ok("open"));
+        assert_eq!(machine.current(), "open");
+        assert_eq!(*log.lock().unwrap(), ["closed.exit", "opening", "open.enter"]);
+
+        machine.trigger("close").unwrap();
+        assert_eq!(machine.current(), "closed");
+        assert_eq!(log.lock().unwrap().last().unwrap(), "closed.enter");
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = door(&log);
+
+        assert_eq!(machine.trigger("lock"), Err(TransitionError::UnknownEvent("lock".to_string())));
+        assert_eq!(
+            machine.trigger("close"),
+            Err(TransitionError::NoTransition { state: "closed".to_string(), event: "close".to_string() })
+        );
+        assert!(!machine.can_trigger("close"));
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_guarded_transition() {
+        let unlocked = Arc::new(AtomicBool::new(false));
+        let unlocked_clone = unlocked.clone();
+
+        let mut machine = StateMachine::new(Fabric::new(), "closed");
+        machine.add_guarded_transition("open", "closed", "open", move || unlocked_clone.load(Ordering::SeqCst));
+
+        assert_eq!(
+            machine.trigger("open"),
+            Err(TransitionError::GuardRejected { state: "closed".to_string(), event: "open".to_string() })
+        );
+        unlocked.store(true, Ordering::SeqCst);
+        assert_eq!(machine.trigger("open"), Ok("open"));
+
+        machine.reset();
+        assert_eq!(machine.current(), "closed");
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut machine = StateMachine::new(Fabric::new(), "idle");
+        machine.add_transition("start", "idle", "running");
+        machine.add_guarded_transition("stop", "running", "idle", || true);
+
+        let expected = "digraph state_machine {\n    rankdir=LR;\n    \"__start\" [shape=point];\n    \"__start\" -> \"idle\";\n    \"idle\" [shape=circle, style=bold];\n    \"running\" [shape=circle];\n    \"idle\" -> \"running\" [label=\"start\"];\n    \"running\" -> \"idle\" [label=\"stop\", style=dashed];\n}\n";
+        assert_eq!(machine.to_dot(), expected);
+    }
+}
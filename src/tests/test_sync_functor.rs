@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::rllt::functor::{KwargsKey, KwargsValue};
+    use crate::rllt::sync_functor::SyncF;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_call_from_many_threads() {
+        let calls = AtomicUsize::new(0);
+        let f = SyncF::new(
+            |args: Vec<u64>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                args.iter().product::<u64>()
+            },
+            vec![3],
+            HashMap::new(),
+        );
+
+        thread::scope(|scope| {
+            for i in 0..8u64 {
+                let f = &f;
+                scope.spawn(move || {
+                    for j in 0..10u64 {
+                        assert_eq!(f.call(vec![j], HashMap::new()), 3 * j);
+                    }
+                    f.call(vec![i], HashMap::new())
+                });
+            }
+        });
+
+        assert_eq!(f.cache_len(), 10);
+        assert_eq!(calls.load(Ordering::SeqCst), 10);
+        let stats = f.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.size), (78, 10, 10));
+    }
+
+    #[test]
+    fn test_single_flight() {
+        let calls = AtomicUsize::new(0);
+        let barrier = Barrier::new(8);
+        let f = SyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                args[0] + 1
+            },
+            vec![],
+            HashMap::new(),
+        );
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    barrier.wait();
+                    assert_eq!(f.call(vec![41], HashMap::new()), 42);
+                });
+            }
+        });
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_panicking_computation_is_retried() {
+        let calls = AtomicUsize::new(0);
+        let f = SyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("first attempt fails");
+                }
+                args[0]
+            },
+            vec![],
+            HashMap::new(),
+        )
+        .shards(1);
+
+        let first = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f.call(vec![5], HashMap::new())));
+        assert!(first.is_err());
+        assert_eq!(f.cache_len(), 0);
+
+        assert_eq!(f.call(vec![5], HashMap::new()), 5);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let calls = AtomicUsize::new(0);
+        let f = SyncF::new(
+            |args: Vec<i32>, _: HashMap<KwargsKey, KwargsValue>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                args[0] * 2
+            },
+            vec![],
+            HashMap::new(),
+        );
+        f.call(vec![1], HashMap::new());
+        f.call(vec![2], HashMap::new());
+
+        assert!(f.invalidate(vec![1], HashMap::new()));
+        assert!(!f.invalidate(vec![1], HashMap::new()));
+        assert_eq!(f.cache_len(), 1);
+
+        f.clear_cache();
+        assert_eq!(f.cache_len(), 0);
+        f.call(vec![2], HashMap::new());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}